@@ -0,0 +1,61 @@
+use super::{Event, GameState, Tile, TileState};
+
+/// How many turns, everyone's counted, what the abilities make lasts
+const WALL_TURNS: u32 = 6;
+const TRAP_TURNS: u32 = 10;
+/// How far from the character an ability reaches
+const RANGE: u16 = 2;
+/// Own turns to wait between two uses
+const RECHARGE: u8 = 3;
+
+/// What a class can do instead of attacking
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Ability {
+    /// A wall for a few turns
+    RaiseWall,
+    /// An armed trap for a few turns
+    SetTrap,
+}
+
+impl Ability {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Ability::RaiseWall => "Raise wall",
+            Ability::SetTrap => "Set trap",
+        }
+    }
+
+    /// The tile the ability makes, and how many turns it stays
+    fn effect(&self) -> (TileState, u32) {
+        match *self {
+            Ability::RaiseWall => (TileState::Wall, WALL_TURNS),
+            Ability::SetTrap => (TileState::Trap, TRAP_TURNS),
+        }
+    }
+}
+
+impl GameState {
+    /// The current character uses its ability on the tile under its selector,
+    /// which has to be free floor within reach. Returns whether it worked.
+    pub fn use_ability(&mut self) -> bool {
+        let (pos, target, ability, recharge) = {
+            let character = &self.characters[self.char_id];
+            (character.pos, character.selector, character.class.ability(), character.recharge)
+        };
+        if recharge > 0 || target == pos || pos.distance(target) > RANGE {
+            return false;
+        }
+        {
+            let tile = self.map.tile(target);
+            if tile.state != TileState::Empty || !tile.is_empty() {
+                return false;
+            }
+        }
+        let (state, turns) = ability.effect();
+        *self.map.tile_mut(target) = Tile::new(state);
+        self.map.temporary.push((target, TileState::Empty, turns));
+        self.characters[self.char_id].recharge = RECHARGE;
+        self.events.push(Event::TileChanged { pos: target });
+        true
+    }
+}
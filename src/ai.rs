@@ -0,0 +1,245 @@
+use input::Command;
+use rng::Rng;
+
+use super::{Action, Character, Direction, GameState, GridPosition, Map, TileState};
+
+const DIRECTIONS: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+
+/// What stepping in an armed trap is worth avoiding, in mp
+const TRAP_PENALTY: u32 = 3;
+
+/// Plays the characters of a team in place of a player, one command at a
+/// time, through the same rules as the keyboard.
+pub trait Controller {
+    /// What to do next with the selected character
+    fn next_command(&mut self, game: &GameState) -> Command;
+}
+
+/// The controller called `name`: `greedy` or `random`
+pub fn controller(name: &str, seed: u64) -> Option<Box<dyn Controller>> {
+    match name {
+        "greedy" => Some(Box::new(Greedy)),
+        "random" => Some(Box::new(Random { rng: Rng::new(seed) })),
+        _ => None,
+    }
+}
+
+impl GameState {
+    /// What the AI of the team in play does next, if the team has one
+    pub fn ai_command(&mut self) -> Option<Command> {
+        let team = self.characters[self.char_id].team;
+        let mut controller = match self.ai.get_mut(team).and_then(|controller| controller.take()) {
+            Some(controller) => controller,
+            None => return None,
+        };
+        let command = controller.next_command(self);
+        self.ai[team] = Some(controller);
+        Some(command)
+    }
+}
+
+/// Where the character aims: in attack mode, or out of mp which comes to
+/// the same as the rules switch to it on the next command
+fn aiming(game: &GameState, character: &Character) -> Option<GridPosition> {
+    match game.action {
+        Action::Attack => Some(character.selector),
+        Action::Move if character.mp == 0 => Some(character.pos),
+        Action::Move => None,
+    }
+}
+
+/// The living enemy the character can attack from `pos`, the weakest first
+fn target(game: &GameState, character: &Character, pos: GridPosition) -> Option<GridPosition> {
+    let high = if game.map.tile(pos).state == TileState::High { 1 } else { 0 };
+    game.characters.iter()
+        .filter(|enemy| enemy.team != character.team && enemy.is_alive())
+        .filter(|enemy| {
+            let distance = pos.distance(enemy.pos);
+            distance > 0 && distance <= character.class.range() + high && game.map.line_of_sight(pos, enemy.pos)
+        })
+        .min_by_key(|enemy| (enemy.hp, enemy.id))
+        .map(|enemy| enemy.pos)
+}
+
+/// The command aiming at `target` and attacking it, or ending the turn
+/// without attacking when there is nothing to hit
+fn aim(selector: GridPosition, pos: GridPosition, target: Option<GridPosition>) -> Command {
+    match target {
+        Some(target) if target == selector => Command::Confirm,
+        Some(target) => Command::Select(target),
+        None if selector == pos => Command::Confirm,
+        None => Command::Cancel,
+    }
+}
+
+/// Walks to where it can hit the weakest enemy and hits it, or towards the
+/// closest enemy when none can be reached this turn
+pub struct Greedy;
+
+impl Controller for Greedy {
+    fn next_command(&mut self, game: &GameState) -> Command {
+        let character = &game.characters[game.char_id];
+        if let Some(selector) = aiming(game, character) {
+            return aim(selector, character.pos, target(game, character, character.pos));
+        }
+        let paths = Paths::new(&game.map, character.pos, character.class.swims(), character.mp as u32);
+        // somewhere to attack from, the cheapest to get to
+        let spot = paths.reached().into_iter()
+            .filter(|&(pos, _)| target(game, character, pos).is_some())
+            .min_by_key(|&(pos, cost)| (cost, pos.as_index(game.map.width)));
+        let goal = match spot {
+            Some((pos, _)) => pos,
+            None => {
+                // on the way to the closest enemy
+                let distances = distances_to_enemies(game, character);
+                let here = distances[character.pos.as_index(game.map.width)];
+                let best = paths.reached().into_iter()
+                    .min_by_key(|&(pos, cost)| (distances[pos.as_index(game.map.width)], cost));
+                match best {
+                    Some((pos, _)) if distances[pos.as_index(game.map.width)] < here => pos,
+                    _ => return Command::EndTurn,
+                }
+            },
+        };
+        match paths.first_step(goal) {
+            Some(dir) => Command::Direction(dir),
+            // already there, time to aim
+            None if spot.is_some() => Command::Confirm,
+            None => Command::EndTurn,
+        }
+    }
+}
+
+/// Wanders around and hits whatever happens to be in range
+pub struct Random {
+    rng: Rng,
+}
+
+impl Controller for Random {
+    fn next_command(&mut self, game: &GameState) -> Command {
+        let character = &game.characters[game.char_id];
+        if let Some(selector) = aiming(game, character) {
+            return aim(selector, character.pos, target(game, character, character.pos));
+        }
+        if self.rng.chance(0.8) {
+            Command::Direction(DIRECTIONS[self.rng.below(4) as usize])
+        } else {
+            Command::Confirm
+        }
+    }
+}
+
+/// The cheapest ways from a tile to the others, within a budget of mp
+pub struct Paths {
+    width: u16,
+    /// For each tile, by index: the cost to get there with traps counted
+    /// in, the mp it takes, and the step that gets there
+    cost: Vec<Option<u32>>,
+    spent: Vec<u32>,
+    came_from: Vec<Option<(GridPosition, Direction)>>,
+}
+
+impl Paths {
+    pub fn new(map: &Map, from: GridPosition, swims: bool, budget: u32) -> Self {
+        let mut paths = Paths {
+            width: map.width,
+            cost: vec![None; map.tiles.len()],
+            spent: vec![0; map.tiles.len()],
+            came_from: vec![None; map.tiles.len()],
+        };
+        paths.cost[from.as_index(map.width)] = Some(0);
+        let mut todo = vec![from];
+        // the maps are small, picking the cheapest by hand is enough
+        while !todo.is_empty() {
+            let index = (0..todo.len()).min_by_key(|&i| paths.cost[todo[i].as_index(map.width)]).unwrap();
+            let pos = todo.swap_remove(index);
+            let here = paths.cost[pos.as_index(map.width)].unwrap();
+            let spent = paths.spent[pos.as_index(map.width)];
+            for dir in DIRECTIONS.iter() {
+                let step = match map.move_cost(pos, dir, swims) {
+                    Some(step) => step as u32,
+                    None => continue,
+                };
+                let next = map.step(pos, dir).unwrap();
+                if spent + step > budget {
+                    continue;
+                }
+                let tile = map.tile(next);
+                let penalty = if tile.state == TileState::Trap && tile.cooldown == 0 { TRAP_PENALTY } else { 0 };
+                let cost = here + step + penalty;
+                let index = next.as_index(map.width);
+                if paths.cost[index].map_or(true, |known| cost < known) {
+                    paths.cost[index] = Some(cost);
+                    paths.spent[index] = spent + step;
+                    paths.came_from[index] = Some((pos, *dir));
+                    todo.push(next);
+                }
+            }
+        }
+        paths
+    }
+
+    /// Every tile that can be reached, with its cost
+    pub fn reached(&self) -> Vec<(GridPosition, u32)> {
+        self.cost.iter().enumerate()
+            .filter_map(|(index, cost)| cost.map(|cost| (position(index, self.width), cost)))
+            .collect()
+    }
+
+    /// The mp it takes to get to `pos`, if it can be reached
+    pub fn spent(&self, pos: GridPosition) -> Option<u32> {
+        let index = pos.as_index(self.width);
+        self.cost[index].map(|_| self.spent[index])
+    }
+
+    /// The first step on the way to `goal`, none if it is where we stand
+    fn first_step(&self, goal: GridPosition) -> Option<Direction> {
+        let mut pos = goal;
+        let mut first = None;
+        while let Some((previous, dir)) = self.came_from[pos.as_index(self.width)] {
+            first = Some(dir);
+            pos = previous;
+        }
+        first
+    }
+}
+
+fn position(index: usize, width: u16) -> GridPosition {
+    GridPosition { x: (index % width as usize) as u16, y: (index / width as usize) as u16 }
+}
+
+/// How many mp it takes to get next to an enemy, from every tile, ignoring
+/// who stands in the way. `u32::MAX` where there is no way.
+fn distances_to_enemies(game: &GameState, character: &Character) -> Vec<u32> {
+    let map = &game.map;
+    let swims = character.class.swims();
+    let mut distances = vec![::std::u32::MAX; map.tiles.len()];
+    let mut todo = Vec::new();
+    for enemy in game.characters.iter().filter(|enemy| enemy.team != character.team && enemy.is_alive()) {
+        distances[enemy.pos.as_index(map.width)] = 0;
+        todo.push(enemy.pos);
+    }
+    while !todo.is_empty() {
+        let index = (0..todo.len()).min_by_key(|&i| distances[todo[i].as_index(map.width)]).unwrap();
+        let pos = todo.swap_remove(index);
+        let here = distances[pos.as_index(map.width)];
+        // going from the next tile to this one costs what this one costs to enter
+        let enter = if here == 0 { 1 } else { map.tile(pos).state.cost(swims).map_or(::std::u32::MAX, |cost| cost as u32) };
+        for dir in DIRECTIONS.iter() {
+            let next = match map.step(pos, dir) {
+                Some(next) => next,
+                None => continue,
+            };
+            if map.tile(next).state.cost(swims).is_none() {
+                continue;
+            }
+            let distance = here.saturating_add(enter);
+            let index = next.as_index(map.width);
+            if distance < distances[index] {
+                distances[index] = distance;
+                todo.push(next);
+            }
+        }
+    }
+    distances
+}
@@ -0,0 +1,143 @@
+use std::collections::VecDeque;
+
+use ggez::graphics;
+
+use super::{Character, Event};
+
+/// How many updates each kind of event takes to play
+const MOVE_FRAMES: u32 = 6;
+const ATTACK_FRAMES: u32 = 12;
+const HIT_FRAMES: u32 = 12;
+const HEAL_FRAMES: u32 = 12;
+const DEATH_FRAMES: u32 = 20;
+
+/// How far an attacker lunges towards its target, in tiles
+const LUNGE: f32 = 0.3;
+
+fn frames(event: &Event) -> u32 {
+    match *event {
+        Event::Moved { .. } => MOVE_FRAMES,
+        Event::Attacked { .. } => ATTACK_FRAMES,
+        Event::Hit { .. } => HIT_FRAMES,
+        Event::Healed { .. } => HEAL_FRAMES,
+        Event::Died { .. } => DEATH_FRAMES,
+        // just long enough for the camera to notice
+        Event::TurnStarted { .. } | Event::TileChanged { .. } => 1,
+    }
+}
+
+/// Plays the rule events one after the other, one step per update. Until an
+/// event is played the characters are drawn as they were before it.
+pub struct Animations {
+    queue: VecDeque<Event>,
+    /// Updates already spent on the event at the front of the queue
+    frame: u32,
+}
+
+impl Animations {
+    pub fn new() -> Self {
+        Animations {
+            queue: VecDeque::new(),
+            frame: 0,
+        }
+    }
+
+    pub fn push(&mut self, event: Event) {
+        self.queue.push_back(event);
+    }
+
+    /// Whether something is still being shown, input waits until it is done
+    pub fn busy(&self) -> bool {
+        !self.queue.is_empty()
+    }
+
+    /// The event being played
+    pub fn current(&self) -> Option<&Event> {
+        self.queue.front()
+    }
+
+    pub fn tick(&mut self) {
+        let done = match self.queue.front() {
+            Some(event) => {
+                self.frame += 1;
+                self.frame >= frames(event)
+            },
+            None => false,
+        };
+        if done {
+            self.queue.pop_front();
+            self.frame = 0;
+        }
+    }
+
+    /// How far we are in the current event, from 0 to 1
+    fn progress(&self) -> f32 {
+        match self.queue.front() {
+            Some(event) => self.frame as f32 / frames(event) as f32,
+            None => 1.0,
+        }
+    }
+
+    /// Where to draw a character: the top left corner of its tile, in map
+    /// coordinates that can fall between two tiles.
+    pub fn position(&self, character: &Character) -> graphics::Point2 {
+        for (i, event) in self.queue.iter().enumerate() {
+            if let Event::Moved { id, from, to } = *event {
+                if id == character.id {
+                    let from = graphics::Point2::new(from.x as f32, from.y as f32);
+                    if i > 0 {
+                        // not our turn yet, we stay where we were
+                        return from;
+                    }
+                    let to = graphics::Point2::new(to.x as f32, to.y as f32);
+                    let t = self.progress();
+                    return graphics::Point2::new(from.x + (to.x - from.x) * t, from.y + (to.y - from.y) * t);
+                }
+            }
+        }
+        let pos = graphics::Point2::new(character.pos.x as f32, character.pos.y as f32);
+        if let Some(&Event::Attacked { id, target }) = self.queue.front() {
+            if id == character.id {
+                // out and back towards the target
+                let target = graphics::Point2::new(target.x as f32, target.y as f32);
+                let (dx, dy) = (target.x - pos.x, target.y - pos.y);
+                let length = (dx * dx + dy * dy).sqrt();
+                let reach = LUNGE * (1.0 - (2.0 * self.progress() - 1.0).abs()) / length;
+                return graphics::Point2::new(pos.x + dx * reach, pos.y + dy * reach);
+            }
+        }
+        pos
+    }
+
+    /// The flash color of a character that is being hit, healed or dying, if any.
+    pub fn tint(&self, character_id: usize) -> Option<graphics::Color> {
+        match self.queue.front() {
+            Some(&Event::Hit { id, .. }) if id == character_id => {
+                if (self.frame / 3) % 2 == 0 {
+                    Some([1.0, 1.0, 1.0, 1.0].into())
+                } else {
+                    Some([0.9, 0.1, 0.1, 1.0].into())
+                }
+            },
+            Some(&Event::Healed { id }) if id == character_id => {
+                if (self.frame / 3) % 2 == 0 {
+                    Some([1.0, 1.0, 1.0, 1.0].into())
+                } else {
+                    Some([0.2, 0.9, 0.3, 1.0].into())
+                }
+            },
+            Some(&Event::Died { id }) if id == character_id => {
+                Some([0.9, 0.1, 0.1, 1.0 - self.progress()].into())
+            },
+            _ => None,
+        }
+    }
+
+    /// Whether to draw a character: the dead are shown until their death is played.
+    pub fn shown(&self, character: &Character) -> bool {
+        character.is_alive() || self.queue.iter().any(|event| match *event {
+            Event::Died { id } => id == character.id,
+            _ => false,
+        })
+    }
+}
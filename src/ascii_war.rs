@@ -1,3 +1,12 @@
+// The first prototype of the game, in the console. Some of it is unused.
+#![allow(dead_code)]
+
+#[macro_use]
+extern crate log;
+
+mod logger;
+
+use std::env;
 use std::io;
 
 const MAX_X: u32 = 7;
@@ -78,44 +87,48 @@ struct Character {
 }
 impl Character {
     fn mv(&mut self, map: &mut [Cell], mov: &str) {
-        println!("old pos = ({},{})", self.x, self.y);
+        debug!("old pos = ({},{})", self.x, self.y);
         match parse(mov) {
             Direction::South if self.y < MAX_Y && map[(self.x + (self.y+1) * (MAX_X+1)) as usize].is_empty() => {
                 map[(self.x + self.y * (MAX_X+1)) as usize].state = CellState::Empty;
                 map[(self.x + (self.y+1) * (MAX_X+1)) as usize].state = CellState::Char;
                 map[(self.x + (self.y+1) * (MAX_X+1)) as usize].id = self.id;
                 self.y = self.y + 1;
-                println!("Moving south")
+                debug!("Moving south")
             },
             Direction::North if self.y > 0 && map[(self.x + (self.y-1) * (MAX_X+1)) as usize].is_empty() => {
                 map[(self.x + self.y * (MAX_X+1)) as usize].state = CellState::Empty;
                 map[(self.x + (self.y-1) * (MAX_X+1)) as usize].state = CellState::Char;
                 map[(self.x + (self.y-1) * (MAX_X+1)) as usize].id = self.id;
                 self.y = self.y - 1;
-                println!("Moving north")
+                debug!("Moving north")
             },
             Direction::West if self.x < MAX_X && map[((self.x+1) + self.y * (MAX_X+1)) as usize].is_empty() => {
                 map[(self.x + self.y * (MAX_X+1)) as usize].state = CellState::Empty;
                 map[((self.x+1) + self.y * (MAX_X+1)) as usize].state = CellState::Char;
                 map[((self.x+1) + self.y * (MAX_X+1)) as usize].id = self.id;
                 self.x = self.x + 1;
-                println!("Moving west")
+                debug!("Moving west")
             },
             Direction::East if self.x > 0 && map[((self.x-1) + self.y * (MAX_X+1)) as usize].is_empty() => {
                 map[(self.x + self.y * (MAX_X+1)) as usize].state = CellState::Empty;
                 map[((self.x-1) + self.y * (MAX_X+1)) as usize].state = CellState::Char;
                 map[((self.x-1) + self.y * (MAX_X+1)) as usize].id = self.id;
                 self.x = self.x - 1;
-                println!("Moving east")
+                debug!("Moving east")
             },
             _ => println!("Can't move this way."),
         };
-        println!("new pos = ({},{})", self.x, self.y);
+        debug!("new pos = ({},{})", self.x, self.y);
     }
 }
 
 
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let log = logger::Settings::from_args(&args).expect("Bad arguments");
+    logger::init(&log).expect("Could not open the log file");
+
     let mut char_id = 0;
 
     let mut char_list = [
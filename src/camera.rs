@@ -0,0 +1,265 @@
+use ggez::{graphics, Context, GameResult};
+
+use super::{GridPosition, Map, GRID_X, GRID_Y, RES_X, RES_Y};
+
+/// Tile sizes we can zoom to, relative to `RES_X` and `RES_Y`
+const ZOOM_LEVELS: [f32; 5] = [0.5, 0.75, 1.0, 1.5, 2.0];
+const DEFAULT_ZOOM: usize = 2;
+
+/// How close to the border of the board the mouse scrolls the view, in pixels
+const EDGE: i32 = 8;
+/// How fast the view scrolls at the border, in tiles per update
+const EDGE_SPEED: f32 = 0.2;
+/// Part of the remaining way to its target the view moves every update
+const GLIDE: f32 = 0.2;
+
+/// Which part of the map is shown, and how big. All the conversions between
+/// tiles and pixels go through here.
+///
+/// The board keeps the `GRID_X` by `GRID_Y` proportions of the original
+/// window: it is scaled to fit the window and centered, with black bars on
+/// the sides that are left.
+pub struct Camera {
+    /// Map coordinates, in tiles, of the top left corner of the view
+    x: f32,
+    y: f32,
+    /// Where the top left corner is gliding to, if anywhere
+    target: Option<(f32, f32)>,
+    zoom: usize,
+    /// How much bigger than `RES_X` by `RES_Y` a tile is at normal zoom,
+    /// depends on the window size
+    scale: f32,
+    /// Size of the window, in pixels
+    window: (f32, f32),
+    /// Where the board is in the window, in pixels
+    left: f32,
+    top: f32,
+    width: f32,
+    height: f32,
+    /// Size of the map, in tiles
+    map_width: f32,
+    map_height: f32,
+    /// Last known mouse position, for edge scrolling
+    mouse: Option<(i32, i32)>,
+}
+
+impl Camera {
+    pub fn new(window_width: f32, window_height: f32, map: &Map) -> Self {
+        let mut camera = Camera {
+            x: 0.0,
+            y: 0.0,
+            target: None,
+            zoom: DEFAULT_ZOOM,
+            scale: 1.0,
+            window: (0.0, 0.0),
+            left: 0.0,
+            top: 0.0,
+            width: 0.0,
+            height: 0.0,
+            map_width: map.width as f32,
+            map_height: map.height as f32,
+            mouse: None,
+        };
+        camera.resize(window_width, window_height);
+        camera
+    }
+
+    /// Fits the board to a new window size, keeping the same tile in the middle.
+    pub fn resize(&mut self, window_width: f32, window_height: f32) {
+        if window_width < 1.0 || window_height < 1.0 {
+            // minimized, nothing to fit
+            return;
+        }
+        let center_x = self.x + self.width / self.tile_width() / 2.0;
+        let center_y = self.y + self.height / self.tile_height() / 2.0;
+        let board_width = (GRID_X * RES_X) as f32;
+        let board_height = (GRID_Y * RES_Y) as f32;
+        self.scale = (window_width / board_width).min(window_height / board_height);
+        self.window = (window_width, window_height);
+        self.width = board_width * self.scale;
+        self.height = board_height * self.scale;
+        self.left = (window_width - self.width) / 2.0;
+        self.top = (window_height - self.height) / 2.0;
+        if center_x.is_finite() && center_y.is_finite() {
+            self.x = center_x - self.width / self.tile_width() / 2.0;
+            self.y = center_y - self.height / self.tile_height() / 2.0;
+        }
+        self.pan(0.0, 0.0);
+    }
+
+    /// Keeps the view on the map after it changed size
+    pub fn set_map_size(&mut self, map: &Map) {
+        self.map_width = map.width as f32;
+        self.map_height = map.height as f32;
+        self.pan(0.0, 0.0);
+    }
+
+    /// Size of a tile on screen, in pixels
+    pub fn tile_width(&self) -> f32 {
+        RES_X as f32 * self.scale * ZOOM_LEVELS[self.zoom]
+    }
+
+    pub fn tile_height(&self) -> f32 {
+        RES_Y as f32 * self.scale * ZOOM_LEVELS[self.zoom]
+    }
+
+    /// Size of the window, in pixels. The HUD goes anywhere in there.
+    pub fn window(&self) -> (f32, f32) {
+        self.window
+    }
+
+    /// Screen rectangle of a tile sized area whose top left corner is at
+    /// `(x, y)` in (possibly fractional) map coordinates.
+    pub fn rect_at(&self, x: f32, y: f32) -> graphics::Rect {
+        graphics::Rect::new(
+            self.left + (x - self.x) * self.tile_width(),
+            self.top + (y - self.y) * self.tile_height(),
+            self.tile_width(),
+            self.tile_height(),
+        )
+    }
+
+    /// Screen rectangle of a tile
+    pub fn rect(&self, pos: GridPosition) -> graphics::Rect {
+        self.rect_at(pos.x as f32, pos.y as f32)
+    }
+
+    /// The tile under a pixel of the window, the inverse of `rect`.
+    pub fn grid_at(&self, x: i32, y: i32) -> Option<GridPosition> {
+        let (x, y) = (x as f32 - self.left, y as f32 - self.top);
+        if x < 0.0 || y < 0.0 || x >= self.width || y >= self.height {
+            // in the black bars
+            return None;
+        }
+        let x = self.x + x / self.tile_width();
+        let y = self.y + y / self.tile_height();
+        if x < 0.0 || y < 0.0 || x >= self.map_width || y >= self.map_height {
+            None
+        } else {
+            Some(GridPosition { x: x as u16, y: y as u16 })
+        }
+    }
+
+    /// Tiles at least partly in view: from `(x0, y0)` included to `(x1, y1)` excluded
+    pub fn visible(&self) -> (u16, u16, u16, u16) {
+        let x1 = (self.x + self.width / self.tile_width()).ceil().min(self.map_width);
+        let y1 = (self.y + self.height / self.tile_height()).ceil().min(self.map_height);
+        (self.x.max(0.0) as u16, self.y.max(0.0) as u16, x1.max(0.0) as u16, y1.max(0.0) as u16)
+    }
+
+    /// Keeps the map in view: centered if it fits, without empty borders otherwise.
+    fn clamp(&self, x: f32, y: f32) -> (f32, f32) {
+        fn axis(start: f32, view: f32, map: f32) -> f32 {
+            if map <= view {
+                (map - view) / 2.0
+            } else {
+                start.max(0.0).min(map - view)
+            }
+        }
+        (
+            axis(x, self.width / self.tile_width(), self.map_width),
+            axis(y, self.height / self.tile_height(), self.map_height),
+        )
+    }
+
+    /// Moves the view by a number of tiles
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        self.target = None;
+        let (x, y) = self.clamp(self.x + dx, self.y + dy);
+        self.x = x;
+        self.y = y;
+    }
+
+    /// Moves the view by a number of pixels, e.g. when dragging the map
+    pub fn drag(&mut self, dx: i32, dy: i32) {
+        let (dx, dy) = (dx as f32 / self.tile_width(), dy as f32 / self.tile_height());
+        self.pan(dx, dy);
+    }
+
+    /// Zooms in (positive) or out (negative) by a number of levels, keeping
+    /// the center of the view in place.
+    pub fn zoom(&mut self, steps: i32) {
+        let zoom = (self.zoom as i32 + steps).max(0).min(ZOOM_LEVELS.len() as i32 - 1) as usize;
+        let center_x = self.x + self.width / self.tile_width() / 2.0;
+        let center_y = self.y + self.height / self.tile_height() / 2.0;
+        self.zoom = zoom;
+        self.x = center_x - self.width / self.tile_width() / 2.0;
+        self.y = center_y - self.height / self.tile_height() / 2.0;
+        self.pan(0.0, 0.0);
+    }
+
+    /// Glides the view until `pos` is in the middle
+    pub fn center_on(&mut self, pos: GridPosition) {
+        let x = pos.x as f32 + 0.5 - self.width / self.tile_width() / 2.0;
+        let y = pos.y as f32 + 0.5 - self.height / self.tile_height() / 2.0;
+        self.target = Some(self.clamp(x, y));
+    }
+
+    /// Puts `pos` in the middle right away
+    pub fn snap_to(&mut self, pos: GridPosition) {
+        self.center_on(pos);
+        if let Some((x, y)) = self.target.take() {
+            self.x = x;
+            self.y = y;
+        }
+    }
+
+    pub fn set_mouse(&mut self, x: i32, y: i32) {
+        self.mouse = Some((x, y));
+    }
+
+    /// Scrolls when the mouse is at the border of the board, and glides to
+    /// the target. Over the black bars, where the HUD can be, it does not.
+    pub fn update(&mut self) {
+        let (left, top) = (self.left as i32, self.top as i32);
+        let (right, bottom) = ((self.left + self.width) as i32, (self.top + self.height) as i32);
+        match self.mouse {
+            Some((x, y)) if x >= left && y >= top && x < right && y < bottom => {
+                let mut dx = 0.0;
+                let mut dy = 0.0;
+                if x < left + EDGE {
+                    dx = -EDGE_SPEED;
+                } else if x >= right - EDGE {
+                    dx = EDGE_SPEED;
+                }
+                if y < top + EDGE {
+                    dy = -EDGE_SPEED;
+                } else if y >= bottom - EDGE {
+                    dy = EDGE_SPEED;
+                }
+                if dx != 0.0 || dy != 0.0 {
+                    self.pan(dx, dy);
+                }
+            },
+            _ => (),
+        }
+        if let Some((x, y)) = self.target {
+            self.x += (x - self.x) * GLIDE;
+            self.y += (y - self.y) * GLIDE;
+            if (x - self.x).abs() < 0.01 && (y - self.y).abs() < 0.01 {
+                self.x = x;
+                self.y = y;
+                self.target = None;
+            }
+        }
+    }
+
+    /// Hides whatever was drawn outside of the board, e.g. tiles half in view.
+    pub fn draw_letterbox(&self, ctx: &mut Context) -> GameResult<()> {
+        let (window_width, window_height) = self.window;
+        let right = self.left + self.width;
+        let bottom = self.top + self.height;
+        graphics::set_color(ctx, graphics::BLACK)?;
+        for bar in [
+            graphics::Rect::new(0.0, 0.0, self.left, window_height),
+            graphics::Rect::new(right, 0.0, window_width - right, window_height),
+            graphics::Rect::new(0.0, 0.0, window_width, self.top),
+            graphics::Rect::new(0.0, bottom, window_width, window_height - bottom),
+        ].iter() {
+            if bar.w > 0.0 && bar.h > 0.0 {
+                graphics::rectangle(ctx, graphics::DrawMode::Fill, *bar)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,273 @@
+use std::fs;
+use std::path::Path;
+
+use ggez::{GameError, GameResult};
+
+use input::Command;
+use objective::Objective;
+use roster::{self, Unit};
+use triggers::Triggers;
+
+use super::{data, Direction, GameState, Map};
+
+/// Where the campaign progress is saved, in the resources
+pub const SAVE: &'static str = "saves/campaign.txt";
+
+/// A battle of the campaign
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    pub name: String,
+    /// Files of the map and of the enemy units, in the resources
+    pub map: String,
+    pub enemies: String,
+    /// Hooks run on what happens during the battle, if any
+    pub triggers: Option<String>,
+    /// Replaces the objective of the map, as an `[objective]` section
+    pub objective: Option<data::Section>,
+    /// Told before the battle, and after it once won
+    pub intro: Vec<String>,
+    pub outro: Vec<String>,
+}
+
+/// What the campaign shows over the board
+#[derive(Debug, Clone)]
+enum Screen {
+    /// The unlocked scenarios to pick from
+    Missions { cursor: usize },
+    /// The intro of the scenario about to be played
+    Briefing(Vec<String>),
+}
+
+/// Scenarios played in order, each one won unlocks the next
+#[derive(Debug, Clone)]
+pub struct Campaign {
+    scenarios: Vec<Scenario>,
+    /// How many scenarios can be played
+    unlocked: usize,
+    /// The scenario being played with its enemies and triggers, if any
+    playing: Option<(usize, Vec<Unit>, Triggers)>,
+    screen: Option<Screen>,
+}
+
+impl Campaign {
+    pub fn load<P: AsRef<Path>>(path: P) -> GameResult<Campaign> {
+        Campaign::from_sections(&data::load(path)?)
+    }
+
+    /// A section per scenario, in the order they are played: its `name`, the
+    /// `map`, `enemies` and optional `triggers` files, `intro` and `outro` lines (as many as
+    /// needed), and optionally an `objective` type with its `tile`, `character_turns`,
+    /// `team` or `leader` as in maps.
+    fn from_sections(sections: &[data::Section]) -> GameResult<Campaign> {
+        let mut scenarios = Vec::new();
+        for section in sections.iter().filter(|section| !section.name.is_empty()) {
+            let lines = |key: &str| -> Vec<String> {
+                section.entries.iter().filter(|entry| entry.0 == key).map(|entry| entry.1.clone()).collect()
+            };
+            let objective = section.get("objective").map(|_| data::Section {
+                name: format!("{} objective", section.name),
+                entries: section.entries.iter()
+                    .map(|&(ref key, ref value)| (if key == "objective" { String::from("type") } else { key.clone() }, value.clone()))
+                    .collect(),
+            });
+            scenarios.push(Scenario {
+                name: section.parse_or("name", section.name.clone())?,
+                map: section.parse("map")?,
+                enemies: section.parse("enemies")?,
+                triggers: section.get("triggers").map(String::from),
+                objective: objective,
+                intro: lines("intro"),
+                outro: lines("outro"),
+            });
+        }
+        if scenarios.is_empty() {
+            return Err(GameError::ResourceLoadError(String::from("the campaign has no scenario")));
+        }
+        Ok(Campaign { scenarios: scenarios, unlocked: 1, playing: None, screen: None })
+    }
+
+    /// Reads how far the campaign went, `unlocked = N`
+    pub fn load_progress<P: AsRef<Path>>(&mut self, path: P) -> GameResult<()> {
+        let sections = data::load(path)?;
+        if let Some(section) = sections.first() {
+            let unlocked: usize = section.parse_or("unlocked", 1)?;
+            self.unlocked = unlocked.max(1).min(self.scenarios.len());
+        }
+        Ok(())
+    }
+
+    pub fn save_progress<P: AsRef<Path>>(&self, path: P) -> GameResult<()> {
+        if let Some(dir) = path.as_ref().parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, format!("unlocked = {}\n", self.unlocked))?;
+        Ok(())
+    }
+
+    /// The enemies of the scenario being played, if any
+    pub fn enemies(&self) -> Option<&[Unit]> {
+        self.playing.as_ref().map(|&(_, ref enemies, _)| enemies.as_slice())
+    }
+
+    /// The hooks of the scenario being played, if any
+    pub fn triggers_mut(&mut self) -> Option<&mut Triggers> {
+        self.playing.as_mut().map(|&mut (_, _, ref mut triggers)| triggers)
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing.is_some()
+    }
+
+    /// Back to free play, e.g. to try out a map of the editor
+    pub fn stop(&mut self) {
+        self.playing = None;
+        self.screen = None;
+    }
+
+    /// What is shown over the board, if anything
+    pub fn lines(&self) -> Option<Vec<String>> {
+        match self.screen {
+            Some(Screen::Missions { cursor }) => {
+                let mut lines = vec![String::from("Campaign"), String::new()];
+                for (index, scenario) in self.scenarios.iter().enumerate() {
+                    lines.push(if index >= self.unlocked {
+                        format!("   {}. (locked)", index + 1)
+                    } else if index == cursor {
+                        format!("> {}. {}", index + 1, scenario.name)
+                    } else {
+                        format!("   {}. {}", index + 1, scenario.name)
+                    });
+                }
+                lines.push(String::new());
+                lines.push(String::from("Arrows: pick, Return / Start: play, Backspace / B: back"));
+                Some(lines)
+            },
+            Some(Screen::Briefing(ref lines)) => Some(lines.clone()),
+            None => None,
+        }
+    }
+}
+
+impl GameState {
+    /// Shows the mission select, if there is a campaign
+    pub fn open_missions(&mut self) {
+        if let Some(ref mut campaign) = self.campaign {
+            campaign.screen = Some(Screen::Missions { cursor: campaign.unlocked - 1 });
+        }
+    }
+
+    /// Gives a command to the campaign screen if one is shown, instead of the
+    /// game. Returns whether it took it.
+    pub fn campaign_command(&mut self, command: &Command) -> bool {
+        let (screen, unlocked) = match self.campaign {
+            Some(ref campaign) => match campaign.screen {
+                Some(ref screen) => (screen.clone(), campaign.unlocked),
+                None => return false,
+            },
+            None => return false,
+        };
+        match (screen, command) {
+            (Screen::Missions { cursor }, &Command::Direction(ref dir)) => {
+                let cursor = match *dir {
+                    Direction::Up => cursor.saturating_sub(1),
+                    Direction::Down => (cursor + 1).min(unlocked - 1),
+                    _ => cursor,
+                };
+                self.set_screen(Some(Screen::Missions { cursor: cursor }));
+            },
+            (Screen::Missions { cursor }, &Command::Confirm) | (Screen::Missions { cursor }, &Command::EndTurn) => {
+                if let Err(e) = self.start_scenario(cursor) {
+                    error!("Cannot play this scenario: {}", e);
+                }
+            },
+            (Screen::Missions { .. }, &Command::Cancel) | (Screen::Briefing(_), &Command::Confirm)
+                | (Screen::Briefing(_), &Command::EndTurn) | (Screen::Briefing(_), &Command::Cancel) => self.set_screen(None),
+            _ => (),
+        }
+        true
+    }
+
+    fn set_screen(&mut self, screen: Option<Screen>) {
+        if let Some(ref mut campaign) = self.campaign {
+            campaign.screen = screen;
+        }
+    }
+
+    /// Starts a scenario picked by its name or its number, from 1, locked or
+    /// not: to try it out straight from the command line.
+    pub fn play_scenario(&mut self, name: &str) -> GameResult<()> {
+        let index = match self.campaign {
+            Some(ref campaign) => campaign.scenarios.iter()
+                .position(|scenario| scenario.name.eq_ignore_ascii_case(name))
+                .or_else(|| name.parse::<usize>().ok().and_then(|number| number.checked_sub(1)))
+                .and_then(|index| campaign.scenarios.get(index).map(|_| index)),
+            None => return Err(GameError::ResourceLoadError(String::from("there is no campaign"))),
+        };
+        match index {
+            Some(index) => self.start_scenario(index),
+            None => Err(GameError::ResourceLoadError(format!("there is no scenario {}", name))),
+        }
+    }
+
+    /// Starts the scenario `index` with its map, enemies and objective, after
+    /// its briefing.
+    fn start_scenario(&mut self, index: usize) -> GameResult<()> {
+        let scenario = match self.campaign {
+            Some(ref campaign) => campaign.scenarios[index].clone(),
+            None => return Ok(()),
+        };
+        let mut map = Map::load(self.resources.join(&scenario.map))?;
+        if let Some(ref section) = scenario.objective {
+            map.objective = Objective::from_section(section, &map)?;
+        }
+        let mut enemies = roster::units(&data::load(self.resources.join(&scenario.enemies))?)?;
+        // the campaign team is always team 0
+        for unit in enemies.iter_mut() {
+            unit.team = 1;
+        }
+        let triggers = match scenario.triggers {
+            Some(ref path) => Triggers::load(self.resources.join(path))?,
+            None => Triggers::default(),
+        };
+        if let Some(ref mut campaign) = self.campaign {
+            campaign.playing = Some((index, enemies, triggers));
+        }
+        let mut briefing = vec![scenario.name.clone(), String::new()];
+        briefing.extend(scenario.intro.iter().cloned());
+        briefing.push(String::new());
+        briefing.push(String::from("Return / Start: begin"));
+        if let Err(e) = self.restart(map) {
+            if let Some(ref mut campaign) = self.campaign {
+                campaign.stop();
+            }
+            return Err(e);
+        }
+        self.set_screen(Some(Screen::Briefing(briefing)));
+        Ok(())
+    }
+
+    /// Once a scenario is over: a victory of team 0 tells the outro and
+    /// unlocks the next one.
+    pub fn finish_scenario(&mut self) {
+        let won = self.outcome.as_ref().map_or(false, |outcome| outcome.winner == Some(0));
+        let path = self.resources.join(SAVE);
+        let campaign = match self.campaign {
+            Some(ref mut campaign) => campaign,
+            None => return,
+        };
+        let index = match campaign.playing {
+            Some((index, _, _)) if won => index,
+            _ => return,
+        };
+        self.progress.extend(campaign.scenarios[index].outro.iter().cloned());
+        if index + 1 == campaign.unlocked && campaign.unlocked < campaign.scenarios.len() {
+            campaign.unlocked += 1;
+            self.progress.push(format!("Unlocked: {}", campaign.scenarios[index + 1].name));
+            if let Err(e) = campaign.save_progress(path) {
+                error!("Could not save the campaign: {}", e);
+            }
+        } else if index + 1 == campaign.scenarios.len() {
+            self.progress.push(String::from("The campaign is over, well done!"));
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use ai;
+use clock::Clock;
+use logger;
+use mapgen::{self, Style};
+use phase::TurnMode;
+use rng::Rng;
+use simulate;
+
+use super::{data, GRID_X, GRID_Y, NB_TEAM, RES_X, RES_Y};
+
+/// What `--help` prints
+pub const USAGE: &'static str = "\
+Usage: rusty_project [OPTIONS]
+
+What to play:
+  --map FILE              play the map of FILE instead of the arena
+  --generate STYLE        play a new map: caves, rooms or arena
+    --size WxH            its size in tiles, 16x16 by default
+    --traps DENSITY       part of its floor covered with traps, 0.03 by default
+    --asymmetric          don't make it the same seen from both sides
+  --seed N                seed of the generated map and of the random AIs
+  --campaign              open the mission select of the campaign
+  --scenario NAME         play a scenario of the campaign, by name or number
+  --load FILE             go on with a game saved with F5
+  --replay FILE           watch a saved game played again
+
+How to play:
+  --ai P1,P2              who plays each team: human, greedy or random
+  --phases                teams play in phases instead of by initiative
+  --turn-time SECONDS     time limit of each turn
+  --clock MINUTES         chess clock, the time of each team for the game
+
+Window:
+  --window WxH            size of the window, 512x512 by default
+  --fullscreen            fill the screen
+
+Simulation:
+  --headless              play AI matches without a window, print statistics
+    --matches N           how many, 100 by default
+    --stats FILE          write the statistics there, as JSON for a .json file
+    --max-turns N         a match that lasts longer is a draw, 200 by default
+
+Logging:
+  --log-level LEVEL       off, error, warn, info (default), debug or trace
+  --log-file FILE         also append the log to FILE
+
+  --help                  print this and quit
+";
+
+/// Everything the command line can ask for
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub map: Option<PathBuf>,
+    pub generate: Option<mapgen::Settings>,
+    pub campaign: bool,
+    pub scenario: Option<String>,
+    pub load: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    /// The controller of each team, `human` for a player
+    pub ai: Vec<String>,
+    /// For what is random: generated maps and AIs
+    pub seed: u64,
+    pub turn_mode: TurnMode,
+    pub clock: Clock,
+    pub window: (u32, u32),
+    pub fullscreen: bool,
+    /// The simulation to run instead of the game, if any
+    pub headless: Option<simulate::Settings>,
+    pub log: logger::Settings,
+    pub help: bool,
+}
+
+impl Options {
+    /// Reads the arguments, refusing those it does not know. The error is
+    /// what to tell the user.
+    pub fn parse(args: &[String]) -> Result<Options, String> {
+        let number = |arg: &str, value: &str| -> Result<u64, String> {
+            value.parse().map_err(|_| format!("{} needs a number, not {}", arg, value))
+        };
+        let mut options = Options {
+            map: None,
+            generate: None,
+            campaign: false,
+            scenario: None,
+            load: None,
+            replay: None,
+            ai: Vec::new(),
+            seed: 0,
+            turn_mode: TurnMode::Initiative,
+            clock: Clock::new(None, None),
+            window: (GRID_X as u32 * RES_X as u32, GRID_Y as u32 * RES_Y as u32),
+            fullscreen: false,
+            headless: None,
+            log: logger::Settings::from_args(args)?,
+            help: false,
+        };
+        let mut seed = None;
+        let mut size = None;
+        let mut traps = None;
+        let mut symmetric = true;
+        let mut headless = false;
+        // options that only mean something with another one, the first one given
+        let mut generator_option = None;
+        let mut simulation_option = None;
+        let mut simulation = simulate::Settings {
+            matches: 100,
+            ai: Vec::new(),
+            stats: None,
+            max_turns: 200,
+            seed: 0,
+            turn_mode: TurnMode::Initiative,
+        };
+        let (mut turn_limit, mut total) = (None, None);
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().map(|value| value.as_str()).ok_or_else(|| format!("{} needs a value", arg));
+            match arg.as_str() {
+                "--map" => options.map = Some(PathBuf::from(value()?)),
+                "--generate" => {
+                    let name = value()?;
+                    let style = Style::from_name(name).ok_or_else(|| format!("unknown map style {}, caves, rooms or arena", name))?;
+                    options.generate = Some(mapgen::Settings::new(style, 0));
+                },
+                "--size" => {
+                    size = Some(dimensions(arg, value()?)?);
+                    generator_option = generator_option.or(Some(arg));
+                },
+                "--traps" => {
+                    traps = Some(value()?.parse::<f32>().map_err(|_| String::from("--traps needs a number"))?);
+                    generator_option = generator_option.or(Some(arg));
+                },
+                "--asymmetric" => {
+                    symmetric = false;
+                    generator_option = generator_option.or(Some(arg));
+                },
+                "--seed" => seed = Some(number(arg, value()?)?),
+                "--campaign" => options.campaign = true,
+                "--scenario" => options.scenario = Some(value()?.to_string()),
+                "--load" => options.load = Some(PathBuf::from(value()?)),
+                "--replay" => options.replay = Some(PathBuf::from(value()?)),
+                "--ai" => options.ai = value()?.split(',').map(String::from).collect(),
+                "--phases" => options.turn_mode = TurnMode::Phases,
+                "--turn-time" => turn_limit = Some(seconds(arg, value()?, 1.0)?),
+                "--clock" => total = Some(seconds(arg, value()?, 60.0)?),
+                "--window" => options.window = dimensions(arg, value()?)?,
+                "--fullscreen" => options.fullscreen = true,
+                "--headless" => headless = true,
+                "--matches" => {
+                    simulation.matches = number(arg, value()?)? as u32;
+                    simulation_option = simulation_option.or(Some(arg));
+                },
+                "--stats" => {
+                    simulation.stats = Some(PathBuf::from(value()?));
+                    simulation_option = simulation_option.or(Some(arg));
+                },
+                "--max-turns" => {
+                    simulation.max_turns = number(arg, value()?)? as u32;
+                    simulation_option = simulation_option.or(Some(arg));
+                },
+                // read by the logger already
+                "--log-level" | "--log-file" => {
+                    value()?;
+                },
+                "--help" | "-h" => options.help = true,
+                other => return Err(format!("unknown option {}, see --help", other)),
+            }
+        }
+        if let (Some(arg), None) = (generator_option, options.generate.as_ref()) {
+            return Err(format!("{} only goes with --generate", arg));
+        }
+        if let (Some(arg), false) = (simulation_option, headless) {
+            return Err(format!("{} only goes with --headless", arg));
+        }
+        let starts = [options.campaign, options.scenario.is_some(), options.load.is_some(), options.replay.is_some()];
+        if starts.iter().filter(|&&start| start).count() > 1 {
+            return Err(String::from("--campaign, --scenario, --load and --replay don't go together"));
+        }
+        if headless && starts.iter().any(|&start| start) {
+            return Err(String::from("--headless plays on a map, not the campaign nor a saved game"));
+        }
+        options.seed = seed.unwrap_or_else(Rng::clock_seed);
+        if let Some(ref mut settings) = options.generate {
+            settings.seed = options.seed;
+            if let Some((width, height)) = size {
+                if width < mapgen::MIN_SIZE || height < mapgen::MIN_SIZE {
+                    return Err(format!("generated maps are at least {}x{}", mapgen::MIN_SIZE, mapgen::MIN_SIZE));
+                }
+                settings.width = width;
+                settings.height = height;
+            }
+            settings.traps = traps.unwrap_or(settings.traps).max(0.0).min(1.0);
+            settings.symmetric = symmetric;
+        }
+        options.clock = Clock::new(turn_limit, total);
+        if options.map.is_some() && options.generate.is_some() {
+            return Err(String::from("--map and --generate don't go together"));
+        }
+        if options.window.0 == 0 || options.window.1 == 0 {
+            return Err(String::from("--window needs a width and a height"));
+        }
+        if !options.ai.is_empty() && options.ai.len() != NB_TEAM {
+            return Err(format!("--ai needs {} controllers, one per team", NB_TEAM));
+        }
+        if let Some(name) = options.ai.iter().find(|name| *name != "human" && ai::controller(name, 0).is_none()) {
+            return Err(format!("unknown controller {}, human, greedy or random", name));
+        }
+        if headless {
+            simulation.ai = if options.ai.is_empty() { vec![String::from("greedy"); NB_TEAM] } else { options.ai.clone() };
+            if simulation.ai.iter().any(|name| name == "human") {
+                return Err(String::from("nobody plays in --headless, every team needs an AI"));
+            }
+            simulation.seed = seed.unwrap_or(0);
+            simulation.turn_mode = options.turn_mode;
+            options.headless = Some(simulation);
+        }
+        if options.ai.is_empty() {
+            options.ai = vec![String::from("human"); NB_TEAM];
+        }
+        Ok(options)
+    }
+}
+
+/// A width and a height written `WxH`
+fn dimensions<T: FromStr>(arg: &str, value: &str) -> Result<(T, T), String> {
+    data::pair(&value.replace('x', " ")).map_err(|_| format!("{} needs a size like 16x16, not {}", arg, value))
+}
+
+/// A time limit given in `unit` seconds
+fn seconds(arg: &str, value: &str, unit: f32) -> Result<Duration, String> {
+    let value: f32 = value.parse().map_err(|_| format!("{} needs a number", arg))?;
+    let time = Duration::from_millis((value.max(0.0) * unit * 1000.0) as u64);
+    if time == Duration::from_millis(0) {
+        return Err(format!("{} needs some time, not {}", arg, value));
+    }
+    Ok(time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Options;
+
+    fn parse(line: &str) -> Result<Options, String> {
+        let args: Vec<String> = line.split_whitespace().map(String::from).collect();
+        Options::parse(&args)
+    }
+
+    #[test]
+    fn the_options_of_a_launch_are_read() {
+        let options = parse("--generate caves --size 20x12 --seed 4 --ai human,greedy --window 800x600").unwrap();
+        let settings = options.generate.unwrap();
+        assert_eq!((settings.width, settings.height, settings.seed), (20, 12, 4));
+        assert_eq!(options.ai, vec!["human", "greedy"]);
+        assert_eq!(options.window, (800, 600));
+        assert!(parse("--headless --matches 3 --stats out.json").unwrap().headless.is_some());
+    }
+
+    #[test]
+    fn map_generation_options_need_generate() {
+        assert!(parse("--size 20x20").is_err());
+        assert!(parse("--traps 0.1").is_err());
+        assert!(parse("--asymmetric").is_err());
+        assert!(parse("--generate rooms --asymmetric").is_ok());
+    }
+
+    #[test]
+    fn simulation_options_need_headless() {
+        assert!(parse("--matches 10").is_err());
+        assert!(parse("--stats out.csv").is_err());
+        assert!(parse("--max-turns 50").is_err());
+    }
+
+    #[test]
+    fn one_way_to_start_at_a_time() {
+        assert!(parse("--campaign --scenario 1").is_err());
+        assert!(parse("--load a.txt --replay b.txt").is_err());
+        assert!(parse("--scenario 2 --load a.txt").is_err());
+        assert!(parse("--replay b.txt").is_ok());
+    }
+
+    #[test]
+    fn headless_plays_no_campaign_nor_save() {
+        assert!(parse("--headless --campaign").is_err());
+        assert!(parse("--headless --scenario 1").is_err());
+        assert!(parse("--headless --load a.txt").is_err());
+        assert!(parse("--headless --replay a.txt").is_err());
+    }
+
+    #[test]
+    fn time_limits_take_some_time() {
+        assert!(parse("--turn-time 0").is_err());
+        assert!(parse("--clock 0").is_err());
+        assert!(parse("--turn-time -5").is_err());
+        assert!(parse("--turn-time 30 --clock 0.5").is_ok());
+    }
+}
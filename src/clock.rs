@@ -0,0 +1,82 @@
+use std::time::Duration;
+
+use super::NB_TEAM;
+
+/// Time limits for competitive play: a limit per turn, and a chess clock
+/// giving each team a total time for the game. Both are optional. The clock
+/// only runs while a player can act, not during animations.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    turn_limit: Option<Duration>,
+    total: Option<Duration>,
+    /// Time spent on the current turn
+    spent: Duration,
+    /// Time each team has left on its chess clock
+    left: Vec<Duration>,
+}
+
+impl Clock {
+    pub fn new(turn_limit: Option<Duration>, total: Option<Duration>) -> Self {
+        Clock {
+            turn_limit: turn_limit,
+            total: total,
+            spent: Duration::from_secs(0),
+            left: vec![total.unwrap_or(Duration::from_secs(0)); NB_TEAM],
+        }
+    }
+
+    /// The time limit of a turn and the total of the chess clock
+    pub fn limits(&self) -> (Option<Duration>, Option<Duration>) {
+        (self.turn_limit, self.total)
+    }
+
+    /// Back to full time, for a new game
+    pub fn restart(&mut self) {
+        *self = Clock::new(self.turn_limit, self.total);
+    }
+
+    /// The player of `team` spent `elapsed` thinking
+    pub fn tick(&mut self, elapsed: Duration, team: usize) {
+        self.spent += elapsed;
+        if self.total.is_some() {
+            self.left[team] = self.left[team].checked_sub(elapsed).unwrap_or(Duration::from_secs(0));
+        }
+    }
+
+    pub fn next_turn(&mut self) {
+        self.spent = Duration::from_secs(0);
+    }
+
+    /// Whether the turn has to end
+    pub fn turn_over(&self) -> bool {
+        self.turn_limit.map_or(false, |limit| self.spent >= limit)
+    }
+
+    /// Whether `team` has no time left on its chess clock, which loses it
+    /// the game
+    pub fn flag_fallen(&self, team: usize) -> bool {
+        self.total.is_some() && self.left[team] == Duration::from_secs(0)
+    }
+
+    /// What the HUD shows, nothing without limits
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if let Some(limit) = self.turn_limit {
+            let left = limit.checked_sub(self.spent).unwrap_or(Duration::from_secs(0));
+            lines.push(format!("Turn: {}", format(left)));
+        }
+        if self.total.is_some() {
+            let clocks: Vec<String> = self.left.iter().enumerate()
+                .map(|(team, &left)| format!("team {} {}", team, format(left)))
+                .collect();
+            lines.push(format!("Clock: {}", clocks.join(", ")));
+        }
+        lines
+    }
+}
+
+/// `m:ss`, rounded up so that 0:00 means the time is up
+fn format(time: Duration) -> String {
+    let seconds = time.as_secs() + if time.subsec_nanos() > 0 { 1 } else { 0 };
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
@@ -0,0 +1,127 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use input::Command;
+
+use super::{GameState, GridPosition, NO_CHAR};
+
+/// Where the state is written when it does not hold together, in the resources
+const DUMPS: &'static str = "saves";
+
+impl GameState {
+    /// What does not hold together in the state of the game, if anything:
+    /// who stands where, positions on the map, hit points, movement points
+    /// and whose turn it is.
+    pub fn inconsistencies(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let map = &self.map;
+        let on_map = |pos: GridPosition| pos.x < map.width && pos.y < map.height;
+        if map.tiles.len() != map.width as usize * map.height as usize {
+            problems.push(format!("{} tiles on a {}x{} map", map.tiles.len(), map.width, map.height));
+            return problems;
+        }
+        for (index, tile) in map.tiles.iter().enumerate() {
+            if tile.char_id == NO_CHAR {
+                continue;
+            }
+            match self.characters.get(tile.char_id) {
+                None => problems.push(format!("tile {} holds character {}, who does not exist", index, tile.char_id)),
+                Some(character) if !character.is_alive() => problems.push(format!("tile {} holds {}, who is dead", index, character.name)),
+                Some(character) if character.pos.as_index(map.width) != index => {
+                    problems.push(format!("tile {} holds {}, who stands on ({}, {})", index, character.name, character.pos.x, character.pos.y))
+                },
+                Some(_) => (),
+            }
+        }
+        for (id, character) in self.characters.iter().enumerate() {
+            if character.id != id {
+                problems.push(format!("{} is character {} but has the id {}", character.name, id, character.id));
+            }
+            if character.hp > character.max_hp {
+                problems.push(format!("{} has {} HP out of {}", character.name, character.hp, character.max_hp));
+            }
+            if character.is_alive() != (character.hp > 0) {
+                problems.push(format!("{} has {} HP and is {:?}", character.name, character.hp, character.state));
+            }
+            if character.mp > character.mobi {
+                problems.push(format!("{} has {} MP out of {}", character.name, character.mp, character.mobi));
+            }
+            if !character.is_alive() {
+                continue;
+            }
+            if !on_map(character.pos) {
+                problems.push(format!("{} stands off the map on ({}, {})", character.name, character.pos.x, character.pos.y));
+            } else if map.tile(character.pos).char_id != id {
+                problems.push(format!("{} stands on ({}, {}) but the tile holds {}", character.name, character.pos.x, character.pos.y,
+                                      map.tile(character.pos).char_id));
+            }
+            if !on_map(character.selector) {
+                problems.push(format!("the selector of {} is off the map", character.name));
+            }
+        }
+        match self.characters.get(self.char_id) {
+            None => problems.push(format!("it is the turn of character {}, who does not exist", self.char_id)),
+            Some(character) if !character.is_alive() && !self.gameover => {
+                problems.push(format!("it is the turn of {}, who is dead", character.name))
+            },
+            Some(_) => (),
+        }
+        if self.records.len() != self.characters.len() {
+            problems.push(format!("{} records for {} characters", self.records.len(), self.characters.len()));
+        }
+        for &(pos, ref key) in map.items.iter() {
+            if !on_map(pos) {
+                problems.push(format!("the {} on ({}, {}) is off the map", key, pos.x, pos.y));
+            }
+        }
+        problems
+    }
+
+    /// Stops the game if `command` left it inconsistent, once its state is
+    /// written to a file to reproduce it. Debug builds run it after every
+    /// command.
+    pub fn check_consistency(&self, command: Command) {
+        let problems = self.inconsistencies();
+        if problems.is_empty() {
+            return;
+        }
+        let path = self.dump_path();
+        match self.dump(&path, command, &problems) {
+            Ok(()) => panic!("the game is inconsistent after {:?}, see {}: {}", command, path.display(), problems.join(", ")),
+            Err(e) => panic!("the game is inconsistent after {:?}, and could not be written to {}: {}; {}",
+                             command, path.display(), e, problems.join(", ")),
+        }
+    }
+
+    fn dump_path(&self) -> PathBuf {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0);
+        self.resources.join(DUMPS).join(format!("inconsistent-{}.txt", time))
+    }
+
+    /// The map file of the board as it is, the rest of the state in comments
+    /// on top: it loads back as a map.
+    fn dump(&self, path: &PathBuf, command: Command, problems: &[String]) -> ::std::io::Result<()> {
+        let mut text = String::from("# The game went inconsistent\n");
+        for problem in problems {
+            text.push_str(&format!("#   {}\n", problem));
+        }
+        text.push_str(&format!("# after {:?}\n#\n", command));
+        text.push_str(&format!("# turn {}, character {} plays, {:?}, {:?} turns\n", self.turn, self.char_id, self.action, self.turn_mode));
+        text.push_str(&format!("# game over: {}, outcome: {:?}, held: {:?}\n", self.gameover, self.outcome, self.held));
+        text.push_str(&format!("# {:?}\n", self.timeline));
+        for (character, record) in self.characters.iter().zip(self.records.iter()) {
+            text.push_str(&format!("# {:?}\n#   {:?}\n", character, record));
+        }
+        text.push_str("#\n# who stands where, by tile index\n");
+        for (index, tile) in self.map.tiles.iter().enumerate().filter(|&(_, tile)| tile.char_id != NO_CHAR) {
+            text.push_str(&format!("#   {}: {}\n", index, tile.char_id));
+        }
+        text.push('\n');
+        text.push_str(&self.map.to_text());
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, text)
+    }
+}
@@ -0,0 +1,77 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+
+use ggez::{GameError, GameResult};
+
+/// A `[name]` block of a data file and its `key = value` entries, in file order.
+/// Entries written before the first header go to a section with an empty name.
+#[derive(Debug, Clone)]
+pub struct Section {
+    pub name: String,
+    pub entries: Vec<(String, String)>,
+}
+
+impl Section {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.iter().find(|e| e.0 == key).map(|e| e.1.as_str())
+    }
+
+    /// Parses the value of `key`, failing if it is missing or malformed.
+    pub fn parse<T: FromStr>(&self, key: &str) -> GameResult<T> {
+        let value = self.get(key).ok_or_else(|| {
+            GameError::ResourceLoadError(format!("[{}] is missing `{}`", self.name, key))
+        })?;
+        value.parse().map_err(|_| {
+            GameError::ResourceLoadError(format!("[{}] has a bad `{}`: {}", self.name, key, value))
+        })
+    }
+
+    /// Like `parse`, but a missing key gives `default`.
+    pub fn parse_or<T: FromStr>(&self, key: &str, default: T) -> GameResult<T> {
+        match self.get(key) {
+            Some(_) => self.parse(key),
+            None => Ok(default),
+        }
+    }
+}
+
+/// Reads our data files: `#` comments, `[section]` headers and `key = value` lines.
+pub fn parse(text: &str) -> GameResult<Vec<Section>> {
+    let mut sections = vec![Section { name: String::new(), entries: Vec::new() }];
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            let name = line[1..line.len() - 1].trim().to_string();
+            sections.push(Section { name: name, entries: Vec::new() });
+        } else if let Some(eq) = line.find('=') {
+            let key = line[..eq].trim().to_string();
+            let value = line[eq + 1..].trim().to_string();
+            sections.last_mut().unwrap().entries.push((key, value));
+        } else {
+            return Err(GameError::ResourceLoadError(format!("line {}: expected `key = value`: {}", number + 1, line)));
+        }
+    }
+    Ok(sections)
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> GameResult<Vec<Section>> {
+    let mut text = String::new();
+    File::open(path.as_ref())
+        .and_then(|mut file| file.read_to_string(&mut text))
+        .map_err(|e| GameError::ResourceLoadError(format!("{}: {}", path.as_ref().display(), e)))?;
+    parse(&text)
+}
+
+/// Parses two whitespace separated numbers, e.g. atlas or map coordinates.
+pub fn pair<T: FromStr>(value: &str) -> GameResult<(T, T)> {
+    let mut parts = value.split_whitespace().map(|v| v.parse::<T>());
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(a)), Some(Ok(b)), None) => Ok((a, b)),
+        _ => Err(GameError::ResourceLoadError(format!("expected two numbers: {}", value))),
+    }
+}
@@ -0,0 +1,189 @@
+use std::path::PathBuf;
+
+use ggez::event::{Keycode, MouseButton};
+use ggez::{graphics, Context, GameResult};
+
+use camera::Camera;
+use hud;
+use tileset::Tileset;
+
+use super::{GridPosition, Map, Tile, TileState, NO_CHAR, TEAM_COLORS};
+
+/// Smallest and biggest maps the editor makes
+const MIN_SIZE: u16 = 3;
+const MAX_SIZE: u16 = 128;
+
+/// What a click paints
+#[derive(Debug, Copy, Clone)]
+enum Brush {
+    Tile(TileState),
+    Spawn(usize),
+}
+
+/// The map editor: paint tiles and spawn points with the mouse, resize the
+/// map, save it, and hand it over to be playtested.
+pub struct Editor {
+    pub map: Map,
+    brush: Brush,
+    /// Cooldown given to the traps we paint
+    cooldown: u32,
+    /// Where the map is saved
+    path: PathBuf,
+    hovered: Option<GridPosition>,
+}
+
+impl Editor {
+    /// Edits `map` as it was loaded, before anyone played on it
+    pub fn new(map: &Map, path: PathBuf) -> Self {
+        let mut map = map.clone();
+        // nobody stands on a map being edited
+        for tile in map.tiles.iter_mut() {
+            tile.char_id = NO_CHAR;
+        }
+        map.temporary.clear();
+        Editor {
+            map: map,
+            brush: Brush::Tile(TileState::Wall),
+            cooldown: 0,
+            path: path,
+            hovered: None,
+        }
+    }
+
+    fn paint(&mut self, pos: GridPosition) {
+        match self.brush {
+            Brush::Tile(state) => {
+                let tile = self.map.tile_mut(pos);
+                *tile = Tile::new(state);
+                tile.cooldown = match state {
+                    TileState::Trap => self.cooldown,
+                    _ => 0,
+                };
+                if !tile.is_empty() {
+                    self.map.spawns.retain(|&(_, spawn)| spawn != pos);
+                }
+            },
+            Brush::Spawn(team) => {
+                if !self.map.spawns.contains(&(team, pos)) {
+                    self.map.spawns.retain(|&(_, spawn)| spawn != pos);
+                    self.map.spawns.push((team, pos));
+                    // spawning in a wall would not go well
+                    if !self.map.tile(pos).is_empty() {
+                        *self.map.tile_mut(pos) = Tile::new(TileState::Empty);
+                    }
+                }
+            },
+        }
+    }
+
+    /// Back to a plain floor, without spawn point or item
+    fn erase(&mut self, pos: GridPosition) {
+        *self.map.tile_mut(pos) = Tile::new(TileState::Empty);
+        self.map.spawns.retain(|&(_, spawn)| spawn != pos);
+        self.map.items.retain(|item| item.0 != pos);
+    }
+
+    fn resize(&mut self, dx: i32, dy: i32, camera: &mut Camera) {
+        let clamp = |size: u16, delta: i32| (size as i32 + delta).max(MIN_SIZE as i32).min(MAX_SIZE as i32) as u16;
+        let (width, height) = (clamp(self.map.width, dx), clamp(self.map.height, dy));
+        self.map.resize(width, height);
+        camera.set_map_size(&self.map);
+    }
+
+    fn save(&self) {
+        match self.map.save(&self.path) {
+            Ok(()) => info!("Map saved to {}", self.path.display()),
+            Err(e) => error!("Could not save the map: {}", e),
+        }
+    }
+
+    /// Throws the changes away and goes back to the saved map
+    fn reload(&mut self, camera: &mut Camera) {
+        match Map::load(&self.path) {
+            Ok(map) => {
+                self.map = map;
+                camera.set_map_size(&self.map);
+            },
+            Err(e) => error!("Could not load the map: {}", e),
+        }
+    }
+
+    pub fn key_down_event(&mut self, keycode: Keycode, camera: &mut Camera) {
+        match keycode {
+            Keycode::Num1 => self.brush = Brush::Tile(TileState::Empty),
+            Keycode::Num2 => self.brush = Brush::Tile(TileState::Wall),
+            Keycode::Num3 => self.brush = Brush::Tile(TileState::Trap),
+            Keycode::Num4 => self.brush = Brush::Spawn(0),
+            Keycode::Num5 => self.brush = Brush::Spawn(1),
+            Keycode::Num6 => self.brush = Brush::Tile(TileState::Rough),
+            Keycode::Num7 => self.brush = Brush::Tile(TileState::Water),
+            Keycode::Num8 => self.brush = Brush::Tile(TileState::High),
+            Keycode::Num9 => self.brush = Brush::Tile(TileState::Cover),
+            Keycode::Num0 => self.brush = Brush::Tile(TileState::Door),
+            Keycode::PageUp => self.cooldown += 1,
+            Keycode::PageDown => self.cooldown = self.cooldown.saturating_sub(1),
+            Keycode::Right => self.resize(1, 0, camera),
+            Keycode::Left => self.resize(-1, 0, camera),
+            Keycode::Down => self.resize(0, 1, camera),
+            Keycode::Up => self.resize(0, -1, camera),
+            Keycode::F5 => self.save(),
+            Keycode::F9 => self.reload(camera),
+            _ => (),
+        }
+    }
+
+    pub fn mouse_button_down_event(&mut self, button: MouseButton, pos: Option<GridPosition>) {
+        if let Some(pos) = pos {
+            match button {
+                MouseButton::Left => self.paint(pos),
+                MouseButton::Right => self.erase(pos),
+                _ => (),
+            }
+        }
+    }
+
+    /// Keeps painting (or erasing) while a button is held
+    pub fn mouse_motion_event(&mut self, left: bool, right: bool, pos: Option<GridPosition>) {
+        self.hovered = pos;
+        if let Some(pos) = pos {
+            if left {
+                self.paint(pos);
+            } else if right {
+                self.erase(pos);
+            }
+        }
+    }
+
+    pub fn draw(&self, ctx: &mut Context, camera: &Camera, tileset: Option<&mut Tileset>, font: &graphics::Font) -> GameResult<()> {
+        match tileset {
+            Some(tileset) => tileset.draw_map(ctx, &self.map, camera)?,
+            None => self.map.draw(ctx, camera)?,
+        }
+        for &(team, pos) in self.map.spawns.iter() {
+            let rect = camera.rect(pos);
+            graphics::set_color(ctx, TEAM_COLORS[team].into())?;
+            let center = graphics::Point2::new(rect.x + rect.w / 2.0, rect.y + rect.h / 2.0);
+            graphics::circle(ctx, graphics::DrawMode::Fill, center, rect.w / 3.0, 0.5)?;
+        }
+        if let Some(pos) = self.hovered {
+            graphics::set_color(ctx, [1.0, 1.0, 1.0, 0.8].into())?;
+            let rect = camera.rect(pos);
+            graphics::rectangle(ctx, graphics::DrawMode::Line(rect.w / 16.0), rect)?;
+        }
+        camera.draw_letterbox(ctx)?;
+
+        let brush = match self.brush {
+            Brush::Tile(TileState::Trap) => format!("trap, cooldown {} (PageUp/PageDown)", self.cooldown),
+            Brush::Tile(state) => String::from(state.key()),
+            Brush::Spawn(team) => format!("spawn of team {}", team),
+        };
+        let lines = [
+            format!("Editor - {}x{} (arrows resize)", self.map.width, self.map.height),
+            format!("Brush: {}", brush),
+            String::from("1 floor 2 wall 3 trap 4-5 spawns, right click erases"),
+            String::from("6 rough 7 water 8 high ground 9 cover 0 door"),
+            format!("F5 saves to {}, F9 reloads it, F3 playtests", self.path.display()),
+        ];
+        hud::draw_panel(ctx, font, &lines, 0.0, 0.0, camera.tile_width() / 8.0)
+    }
+}
@@ -0,0 +1,95 @@
+use ggez::{graphics, Context, GameResult};
+
+/// Draws a few lines of text in a box next to the hovered tile, kept inside the window.
+pub fn draw_tooltip(ctx: &mut Context, font: &graphics::Font, lines: &[String], tile: graphics::Rect, window: (f32, f32)) -> GameResult<()> {
+    // space between the border and the text, grows with the tiles
+    let padding = (tile.w / 8.0).max(2.0);
+    let texts = render(ctx, font, lines)?;
+    let (width, height) = size(&texts, padding);
+    let mut x = tile.x + tile.w;
+    let mut y = tile.y + tile.h;
+    if x + width > window.0 {
+        x = (tile.x - width).max(0.0);
+    }
+    if y + height > window.1 {
+        y = (tile.y - height).max(0.0);
+    }
+    draw_texts(ctx, &texts, x, y, padding)
+}
+
+/// Draws a few lines of text in a box whose top left corner is at `(x, y)`.
+pub fn draw_panel(ctx: &mut Context, font: &graphics::Font, lines: &[String], x: f32, y: f32, padding: f32) -> GameResult<()> {
+    let texts = render(ctx, font, lines)?;
+    draw_texts(ctx, &texts, x, y, padding.max(2.0))
+}
+
+/// Draws a few lines of text in a box in the middle of the window.
+pub fn draw_centered(ctx: &mut Context, font: &graphics::Font, lines: &[String], window: (f32, f32), padding: f32) -> GameResult<()> {
+    let padding = padding.max(2.0);
+    let texts = render(ctx, font, lines)?;
+    let (width, height) = size(&texts, padding);
+    draw_texts(ctx, &texts, ((window.0 - width) / 2.0).max(0.0), ((window.1 - height) / 2.0).max(0.0), padding)
+}
+
+/// Draws a few lines of text in a box in the top right corner of the window.
+pub fn draw_top_right(ctx: &mut Context, font: &graphics::Font, lines: &[String], window: (f32, f32), padding: f32) -> GameResult<()> {
+    let padding = padding.max(2.0);
+    let texts = render(ctx, font, lines)?;
+    let (width, _) = size(&texts, padding);
+    draw_texts(ctx, &texts, (window.0 - width).max(0.0), 0.0, padding)
+}
+
+/// Darkens the whole window, under a menu
+pub fn draw_shade(ctx: &mut Context, window: (f32, f32)) -> GameResult<()> {
+    graphics::set_color(ctx, [0.0, 0.0, 0.0, 0.6].into())?;
+    graphics::rectangle(ctx, graphics::DrawMode::Fill, graphics::Rect::new(0.0, 0.0, window.0, window.1))
+}
+
+/// Draws the next turns as a strip of boxes at the bottom of the window,
+/// the current one first and bigger. `entries` are a label and a team color.
+pub fn draw_timeline(ctx: &mut Context, font: &graphics::Font, entries: &[(String, graphics::Color)], window: (f32, f32), size: f32) -> GameResult<()> {
+    let size = size.max(16.0);
+    let gap = size / 8.0;
+    let width = size * 1.5 + (entries.len() as f32 - 1.0) * (size + gap);
+    let mut x = (window.0 - width) / 2.0;
+    for (i, &(ref label, color)) in entries.iter().enumerate() {
+        let side = if i == 0 { size * 1.5 } else { size };
+        let rect = graphics::Rect::new(x, window.1 - side - gap, side, side);
+        graphics::set_color(ctx, color)?;
+        graphics::rectangle(ctx, graphics::DrawMode::Fill, rect)?;
+        graphics::set_color(ctx, [1.0, 1.0, 1.0, 1.0].into())?;
+        let text = graphics::Text::new(ctx, label, font)?;
+        let dest = graphics::Point2::new(rect.x + (side - text.width() as f32) / 2.0, rect.y + (side - text.height() as f32) / 2.0);
+        graphics::draw(ctx, &text, dest, 0.0)?;
+        x += side + gap;
+    }
+    Ok(())
+}
+
+fn render(ctx: &mut Context, font: &graphics::Font, lines: &[String]) -> GameResult<Vec<graphics::Text>> {
+    let mut texts = Vec::with_capacity(lines.len());
+    for line in lines {
+        texts.push(graphics::Text::new(ctx, line, font)?);
+    }
+    Ok(texts)
+}
+
+/// Size of the box around some lines of text
+fn size(texts: &[graphics::Text], padding: f32) -> (f32, f32) {
+    let width = texts.iter().map(|t| t.width()).max().unwrap_or(0) as f32 + 2.0 * padding;
+    let height = texts.iter().map(|t| t.height()).sum::<u32>() as f32 + 2.0 * padding;
+    (width, height)
+}
+
+fn draw_texts(ctx: &mut Context, texts: &[graphics::Text], x: f32, y: f32, padding: f32) -> GameResult<()> {
+    let (width, height) = size(texts, padding);
+    graphics::set_color(ctx, [0.0, 0.0, 0.0, 0.8].into())?;
+    graphics::rectangle(ctx, graphics::DrawMode::Fill, graphics::Rect::new(x, y, width, height))?;
+    graphics::set_color(ctx, [1.0, 1.0, 1.0, 1.0].into())?;
+    let mut line_y = y + padding;
+    for text in texts.iter() {
+        graphics::draw(ctx, text, graphics::Point2::new(x + padding, line_y), 0.0)?;
+        line_y += text.height() as f32;
+    }
+    Ok(())
+}
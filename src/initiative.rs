@@ -0,0 +1,67 @@
+use super::Character;
+
+/// Time a turn takes for a character of speed 1. A character of speed `s`
+/// gets a turn every `TURN_LENGTH / s`, so faster ones play more often.
+pub const TURN_LENGTH: u32 = 1200;
+
+/// Who plays when. Each character has the time of its next turn, and the
+/// living character with the earliest one plays, the lowest id on a tie.
+#[derive(Debug, Clone)]
+pub struct Timeline {
+    next: Vec<u32>,
+}
+
+impl Timeline {
+    /// Everyone is ready at the start, in id order
+    pub fn new(characters: &[Character]) -> Self {
+        Timeline { next: vec![0; characters.len()] }
+    }
+
+    /// A character joins the game and plays once the current turn is over.
+    /// `characters` are those already there, before it joins them.
+    pub fn join(&mut self, characters: &[Character]) {
+        let now = self.current(characters).map_or(0, |current| self.next[current]);
+        self.next.push(now);
+    }
+
+    /// Whose turn it is
+    pub fn current(&self, characters: &[Character]) -> Option<usize> {
+        Timeline::earliest(&self.next, characters)
+    }
+
+    fn earliest(next: &[u32], characters: &[Character]) -> Option<usize> {
+        characters.iter()
+            .filter(|character| character.is_alive())
+            .min_by_key(|character| (next[character.id], character.id))
+            .map(|character| character.id)
+    }
+
+    /// The character played, its next turn comes after `cost` (a part of
+    /// its usual time between two turns, 1 for a whole turn).
+    pub fn finish_turn(&mut self, character: &Character, cost: f32) {
+        self.next[character.id] += ((TURN_LENGTH / character.class.speed()) as f32 * cost) as u32;
+    }
+
+    /// Pushes the next turn of a character back, or forward with a negative
+    /// `amount`. It can't come before the turn being played.
+    pub fn delay(&mut self, id: usize, amount: i32, characters: &[Character]) {
+        let now = self.current(characters).map_or(0, |current| self.next[current]) as i32;
+        self.next[id] = (self.next[id] as i32 + amount).max(now) as u32;
+    }
+
+    /// The next `count` turns, starting with the current one
+    pub fn forecast(&self, characters: &[Character], count: usize) -> Vec<usize> {
+        let mut next = self.next.clone();
+        let mut turns = Vec::with_capacity(count);
+        while turns.len() < count {
+            match Timeline::earliest(&next, characters) {
+                Some(id) => {
+                    turns.push(id);
+                    next[id] += TURN_LENGTH / characters[id].class.speed();
+                },
+                None => break,
+            }
+        }
+        turns
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+
+use ggez::event::{Axis, Button, Keycode};
+
+use super::{Direction, GridPosition, NB_TEAM};
+
+/// How far a stick has to be pushed before it counts as a d-pad press.
+const STICK_THRESHOLD: i16 = 16000;
+
+/// Everything a player can ask the game to do, whatever the device.
+#[derive(Debug, Copy, Clone)]
+pub enum Command {
+    /// Move the character or the attack selector
+    Direction(Direction),
+    /// Put the attack selector on a tile
+    Select(GridPosition),
+    /// Go to the attack phase, or attack and end the turn
+    Confirm,
+    /// Put the attack selector back on the character
+    Cancel,
+    /// Use the ability of the character on the selector instead of attacking
+    Ability,
+    /// Select the next item in the bag of the character
+    NextItem,
+    /// Use the selected item on the selector instead of attacking
+    UseItem,
+    /// Open or close the door the character faces
+    Interact,
+    /// End the turn right away
+    EndTurn,
+    /// Select the next character of the team that still has to play, in phases mode
+    NextUnit,
+    /// End the turn of every character of the team, in phases mode
+    EndPhase,
+}
+
+impl Command {
+    /// How it is written in replays, e.g. `right` or `select 3 4`
+    pub fn to_text(&self) -> String {
+        match *self {
+            Command::Direction(ref dir) => String::from(match *dir {
+                Direction::Up => "up",
+                Direction::Down => "down",
+                Direction::Left => "left",
+                Direction::Right => "right",
+            }),
+            Command::Select(pos) => format!("select {} {}", pos.x, pos.y),
+            Command::Confirm => String::from("confirm"),
+            Command::Cancel => String::from("cancel"),
+            Command::Ability => String::from("ability"),
+            Command::NextItem => String::from("next_item"),
+            Command::UseItem => String::from("use_item"),
+            Command::Interact => String::from("interact"),
+            Command::EndTurn => String::from("end_turn"),
+            Command::NextUnit => String::from("next_unit"),
+            Command::EndPhase => String::from("end_phase"),
+        }
+    }
+
+    pub fn from_text(text: &str) -> Option<Command> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        match words.as_slice() {
+            ["up"] => Some(Command::Direction(Direction::Up)),
+            ["down"] => Some(Command::Direction(Direction::Down)),
+            ["left"] => Some(Command::Direction(Direction::Left)),
+            ["right"] => Some(Command::Direction(Direction::Right)),
+            ["select", x, y] => match (x.parse(), y.parse()) {
+                (Ok(x), Ok(y)) => Some(Command::Select(GridPosition { x: x, y: y })),
+                _ => None,
+            },
+            ["confirm"] => Some(Command::Confirm),
+            ["cancel"] => Some(Command::Cancel),
+            ["ability"] => Some(Command::Ability),
+            ["next_item"] => Some(Command::NextItem),
+            ["use_item"] => Some(Command::UseItem),
+            ["interact"] => Some(Command::Interact),
+            ["end_turn"] => Some(Command::EndTurn),
+            ["next_unit"] => Some(Command::NextUnit),
+            ["end_phase"] => Some(Command::EndPhase),
+            _ => None,
+        }
+    }
+
+    pub fn from_keycode(key: Keycode) -> Option<Command> {
+        if let Some(dir) = Direction::from_keycode(key) {
+            return Some(Command::Direction(dir));
+        }
+        match key {
+            Keycode::Space => Some(Command::Confirm),
+            Keycode::Backspace | Keycode::Escape => Some(Command::Cancel),
+            Keycode::Q => Some(Command::Ability),
+            Keycode::E => Some(Command::Interact),
+            Keycode::I => Some(Command::NextItem),
+            Keycode::U => Some(Command::UseItem),
+            Keycode::Return => Some(Command::EndTurn),
+            Keycode::Tab => Some(Command::NextUnit),
+            Keycode::P => Some(Command::EndPhase),
+            _ => None,
+        }
+    }
+
+    pub fn from_button(btn: Button) -> Option<Command> {
+        if let Some(dir) = Direction::from_button(btn) {
+            return Some(Command::Direction(dir));
+        }
+        match btn {
+            Button::A => Some(Command::Confirm),
+            Button::B => Some(Command::Cancel),
+            Button::X => Some(Command::Interact),
+            Button::LeftStick => Some(Command::NextItem),
+            Button::RightStick => Some(Command::UseItem),
+            Button::RightShoulder => Some(Command::Ability),
+            Button::Y | Button::Start => Some(Command::EndTurn),
+            Button::LeftShoulder => Some(Command::NextUnit),
+            Button::Back => Some(Command::EndPhase),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ControlMode {
+    /// Every gamepad plays whoever's turn it is (hot-seat)
+    Shared,
+    /// Each gamepad only plays the characters of its own team
+    PerTeam,
+}
+
+/// Keeps track of which gamepad belongs to which team.
+pub struct Controls {
+    pub mode: ControlMode,
+    /// Gamepad instance id -> team, filled as gamepads are first used
+    teams: HashMap<i32, usize>,
+    /// Whether a stick axis (x or y) is already pushed, so one push gives one step
+    pushed: HashMap<(i32, bool), bool>,
+}
+
+impl Controls {
+    pub fn new() -> Self {
+        Controls {
+            mode: ControlMode::Shared,
+            teams: HashMap::new(),
+            pushed: HashMap::new(),
+        }
+    }
+
+    pub fn toggle_mode(&mut self) {
+        self.mode = match self.mode {
+            ControlMode::Shared => ControlMode::PerTeam,
+            ControlMode::PerTeam => ControlMode::Shared,
+        };
+        info!("Control mode: {:?}", self.mode);
+    }
+
+    /// The team of a gamepad. A gamepad we have never seen gets the next team.
+    pub fn team_of(&mut self, instance_id: i32) -> usize {
+        let next = self.teams.len() % NB_TEAM;
+        *self.teams.entry(instance_id).or_insert_with(|| {
+            info!("Gamepad {} plays team {}", instance_id, next);
+            next
+        })
+    }
+
+    /// Whether a gamepad may send commands while `team` is playing.
+    pub fn allows(&mut self, instance_id: i32, team: usize) -> bool {
+        let pad_team = self.team_of(instance_id);
+        match self.mode {
+            ControlMode::Shared => true,
+            ControlMode::PerTeam => pad_team == team,
+        }
+    }
+
+    /// Turns left stick motion into d-pad like commands.
+    pub fn stick(&mut self, axis: Axis, value: i16, instance_id: i32) -> Option<Command> {
+        let dir = match axis {
+            Axis::LeftX if value > STICK_THRESHOLD => Direction::Right,
+            Axis::LeftX if value < -STICK_THRESHOLD => Direction::Left,
+            Axis::LeftY if value > STICK_THRESHOLD => Direction::Down,
+            Axis::LeftY if value < -STICK_THRESHOLD => Direction::Up,
+            Axis::LeftX | Axis::LeftY => {
+                // back to the center, the next push is a new step
+                if value > -STICK_THRESHOLD / 2 && value < STICK_THRESHOLD / 2 {
+                    self.pushed.insert((instance_id, axis == Axis::LeftX), false);
+                }
+                return None;
+            }
+            _ => return None,
+        };
+        let pushed = self.pushed.entry((instance_id, axis == Axis::LeftX)).or_insert(false);
+        if *pushed {
+            None
+        } else {
+            *pushed = true;
+            Some(Command::Direction(dir))
+        }
+    }
+}
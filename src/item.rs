@@ -0,0 +1,229 @@
+use std::path::Path;
+
+use ggez::{graphics, Context, GameError, GameResult};
+
+use camera::Camera;
+
+use super::{data, Event, GameState, Tile, TileState, NO_CHAR};
+
+/// The items we know when `items.txt` can't be read
+pub const DEFAULT_ITEMS: &'static str = include_str!("../resources/items.txt");
+
+/// What using an item does
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Effect {
+    Heal,
+    Damage,
+    Trap,
+}
+
+impl Effect {
+    /// Name of the effect in `items.txt`
+    fn key(&self) -> &'static str {
+        match *self {
+            Effect::Heal => "heal",
+            Effect::Damage => "damage",
+            Effect::Trap => "trap",
+        }
+    }
+}
+
+/// A kind of consumable, as described in `items.txt`
+#[derive(Debug, Clone)]
+pub struct Item {
+    pub key: String,
+    pub name: String,
+    pub effect: Effect,
+    pub power: u8,
+    pub range: u16,
+    pub radius: u16,
+    pub color: [f32; 4],
+}
+
+/// Every kind of item, by key
+#[derive(Debug, Clone)]
+pub struct Items {
+    items: Vec<Item>,
+}
+
+impl Items {
+    pub fn parse(text: &str) -> GameResult<Items> {
+        Items::from_sections(&data::parse(text)?)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> GameResult<Items> {
+        Items::from_sections(&data::load(path)?)
+    }
+
+    pub fn from_sections(sections: &[data::Section]) -> GameResult<Items> {
+        let mut items = Vec::new();
+        for section in sections.iter().filter(|section| !section.name.is_empty()) {
+            let error = |message: String| GameError::ResourceLoadError(format!("item {}: {}", section.name, message));
+            let effect = match section.parse::<String>("effect")?.as_str() {
+                "heal" => Effect::Heal,
+                "damage" => Effect::Damage,
+                "trap" => Effect::Trap,
+                other => return Err(error(format!("unknown effect {}", other))),
+            };
+            let color: Vec<f32> = section.get("color").unwrap_or("1 1 1").split_whitespace()
+                .map(|value| value.parse().map_err(|_| error(format!("bad color {}", value))))
+                .collect::<GameResult<_>>()?;
+            if color.len() != 3 {
+                return Err(error(String::from("a color is three numbers")));
+            }
+            items.push(Item {
+                key: section.name.clone(),
+                name: section.parse_or("name", section.name.clone())?,
+                effect: effect,
+                power: section.parse_or("power", 0)?,
+                range: section.parse_or("range", 0)?,
+                radius: section.parse_or("radius", 0)?,
+                color: [color[0], color[1], color[2], 1.0],
+            });
+        }
+        Ok(Items { items: items })
+    }
+
+    /// The file giving back these items
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for item in self.items.iter() {
+            text.push_str(&format!("[{}]\nname = {}\neffect = {}\npower = {}\nrange = {}\nradius = {}\ncolor = {} {} {}\n\n",
+                                   item.key, item.name, item.effect.key(), item.power, item.range, item.radius,
+                                   item.color[0], item.color[1], item.color[2]));
+        }
+        text
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Item> {
+        self.items.iter().find(|item| item.key == key)
+    }
+
+    /// What the HUD calls an item
+    pub fn name<'a>(&'a self, key: &'a str) -> &'a str {
+        self.get(key).map_or(key, |item| item.name.as_str())
+    }
+}
+
+impl GameState {
+    /// Puts whatever lies on the tile of a character in its bag
+    pub fn pick_up(&mut self, id: usize) {
+        let pos = self.characters[id].pos;
+        let (found, left): (Vec<_>, Vec<_>) = self.map.items.drain(..).partition(|item| item.0 == pos);
+        self.map.items = left;
+        if found.is_empty() {
+            return;
+        }
+        self.characters[id].inventory.extend(found.into_iter().map(|(_, key)| key));
+        self.events.push(Event::TileChanged { pos: pos });
+    }
+
+    /// Selects the next item in the bag of the current character
+    pub fn cycle_item(&mut self) {
+        let character = &mut self.characters[self.char_id];
+        if !character.inventory.is_empty() {
+            character.item = (character.item + 1) % character.inventory.len();
+        }
+    }
+
+    /// The current character uses its selected item on the tile under its
+    /// selector. Returns whether it did, the item is gone then.
+    pub fn use_item(&mut self) -> bool {
+        let (id, pos, target) = {
+            let character = &self.characters[self.char_id];
+            (character.id, character.pos, character.selector)
+        };
+        let item = match self.characters[id].inventory.get(self.characters[id].item).and_then(|key| self.items.get(key)) {
+            Some(item) => item.clone(),
+            None => return false,
+        };
+        if pos.distance(target) > item.range || !self.map.line_of_sight(pos, target) {
+            return false;
+        }
+        match item.effect {
+            Effect::Heal => {
+                let patient = self.map.tile(target).char_id;
+                if patient == NO_CHAR {
+                    return false;
+                }
+                self.events.push(Event::Healed { id: patient });
+                let patient = &mut self.characters[patient];
+                patient.hp = patient.hp.saturating_add(item.power).min(patient.max_hp);
+            },
+            Effect::Damage => {
+                self.events.push(Event::Attacked { id: id, target: target });
+                let radius = item.radius as i32;
+                for dy in -radius..radius + 1 {
+                    for dx in -radius..radius + 1 {
+                        let (x, y) = (target.x as i32 + dx, target.y as i32 + dy);
+                        if dx.abs() + dy.abs() > radius || x < 0 || y < 0 || x >= self.map.width as i32 || y >= self.map.height as i32 {
+                            continue;
+                        }
+                        let blast = (x as u16, y as u16).into();
+                        let victim = self.map.tile(blast).char_id;
+                        if victim != NO_CHAR {
+                            self.hurt(victim, item.power, Some(id));
+                        } else {
+                            self.break_wall(blast, item.power);
+                        }
+                    }
+                }
+            },
+            Effect::Trap => {
+                {
+                    let tile = self.map.tile(target);
+                    if tile.state != TileState::Empty || !tile.is_empty() {
+                        return false;
+                    }
+                }
+                *self.map.tile_mut(target) = Tile::new(TileState::Trap);
+                self.events.push(Event::TileChanged { pos: target });
+            },
+        }
+        let character = &mut self.characters[id];
+        character.inventory.remove(character.item);
+        if character.item >= character.inventory.len() {
+            character.item = 0;
+        }
+        true
+    }
+
+    /// Draws the items lying on the map in view, as little gems
+    pub fn draw_items(&self, ctx: &mut Context, camera: &Camera) -> GameResult<()> {
+        let (x0, y0, x1, y1) = camera.visible();
+        for &(pos, ref key) in self.map.items.iter() {
+            if pos.x < x0 || pos.y < y0 || pos.x >= x1 || pos.y >= y1 {
+                continue;
+            }
+            let rect = camera.rect(pos);
+            let color = self.items.get(key).map_or([1.0, 1.0, 1.0, 1.0], |item| item.color);
+            let (cx, cy, r) = (rect.x + rect.w / 2.0, rect.y + rect.h / 2.0, rect.w / 5.0);
+            let points = [
+                graphics::Point2::new(cx, cy - r),
+                graphics::Point2::new(cx + r, cy),
+                graphics::Point2::new(cx, cy + r),
+                graphics::Point2::new(cx - r, cy),
+            ];
+            graphics::set_color(ctx, color.into())?;
+            graphics::polygon(ctx, graphics::DrawMode::Fill, &points)?;
+            graphics::set_color(ctx, graphics::WHITE)?;
+            graphics::polygon(ctx, graphics::DrawMode::Line(1.0), &points)?;
+        }
+        Ok(())
+    }
+
+    /// The bag of the current character, for the HUD
+    pub fn inventory_lines(&self) -> Vec<String> {
+        let character = &self.characters[self.char_id];
+        if character.inventory.is_empty() {
+            return Vec::new();
+        }
+        let names: Vec<String> = character.inventory.iter().enumerate()
+            .map(|(i, key)| {
+                let name = self.items.name(key);
+                if i == character.item { format!("[{}]", name) } else { name.to_string() }
+            })
+            .collect();
+        vec![format!("Items: {}", names.join(", "))]
+    }
+}
@@ -0,0 +1,90 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use log::{self, LevelFilter, Log, Metadata, Record};
+
+/// What gets logged when the command line does not say: what went wrong,
+/// and the few things worth knowing, like the seed of a generated map.
+const DEFAULT_LEVEL: LevelFilter = LevelFilter::Info;
+
+/// How much to log, and where besides the standard error
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub level: LevelFilter,
+    pub file: Option<PathBuf>,
+}
+
+impl Settings {
+    /// Reads `--log-level LEVEL`, one of `off`, `error`, `warn`, `info`,
+    /// `debug` (the rules) or `trace` (every command and input event), and
+    /// `--log-file FILE`.
+    pub fn from_args(args: &[String]) -> Result<Settings, String> {
+        let mut settings = Settings { level: DEFAULT_LEVEL, file: None };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--log-level" => {
+                    let value = args.next().ok_or_else(|| String::from("--log-level needs a level"))?;
+                    settings.level = value.parse().map_err(|_| format!("unknown log level {}", value))?;
+                },
+                "--log-file" => {
+                    let value = args.next().ok_or_else(|| String::from("--log-file needs a file"))?;
+                    settings.file = Some(PathBuf::from(value));
+                },
+                _ => (),
+            }
+        }
+        Ok(settings)
+    }
+}
+
+/// Writes each message on a line of the standard error, and of the log file
+/// if there is one. The standard output is left to what the game prints on
+/// purpose, like statistics.
+struct Logger {
+    level: LevelFilter,
+    file: Option<Mutex<File>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!("[{}] {}: {}", record.level(), record.target(), record.args());
+        eprintln!("{}", line);
+        if let Some(ref file) = self.file {
+            if let Ok(mut file) = file.lock() {
+                // nowhere left to tell about it
+                let _ = writeln!(file, "{}", line);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(ref file) = self.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = file.flush();
+            }
+        }
+    }
+}
+
+/// Sends the `log` macros to the standard error and the log file, once and
+/// for the whole run.
+pub fn init(settings: &Settings) -> io::Result<()> {
+    let file = match settings.file {
+        Some(ref path) => Some(Mutex::new(OpenOptions::new().create(true).append(true).open(path)?)),
+        None => None,
+    };
+    let logger = Box::new(Logger { level: settings.level, file: file });
+    log::set_logger(Box::leak(logger)).map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+    log::set_max_level(settings.level);
+    Ok(())
+}
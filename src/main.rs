@@ -1,21 +1,90 @@
 extern crate ggez;
+#[macro_use]
+extern crate log;
+
+mod ability;
+mod ai;
+mod animation;
+mod camera;
+mod campaign;
+mod cli;
+mod clock;
+mod consistency;
+mod data;
+mod editor;
+mod hud;
+mod initiative;
+mod input;
+mod item;
+mod logger;
+mod mapfile;
+mod mapgen;
+mod objective;
+mod overlay;
+mod phase;
+mod replay;
+mod rng;
+mod roster;
+mod scene;
+mod simulate;
+mod tileset;
+mod triggers;
+#[cfg(test)]
+mod tests;
 
 // use std::io;
-use ggez::event::Keycode;
-use ggez::{event, graphics, Context, GameResult};
+use ggez::event::{Axis, Button, Keycode, MouseButton, MouseState};
+use ggez::{event, graphics, Context, GameError, GameResult};
 use ggez::timer;
+use ability::Ability;
+use ai::Controller;
+use animation::Animations;
+use camera::Camera;
+use campaign::Campaign;
+use clock::Clock;
+use editor::Editor;
+use initiative::{Timeline, TURN_LENGTH};
+use input::{Command, Controls};
+use item::Items;
+use objective::{Objective, Outcome, Record};
+use phase::TurnMode;
+use roster::Roster;
+use scene::SceneStack;
+use std::collections::VecDeque;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process;
 use std::time::{Duration, Instant};
+use tileset::Tileset;
+use triggers::Trigger;
 
+/// Size of the default map, and of the window in tiles
 const GRID_X: u16 = 16;
 const GRID_Y: u16 = 16;
 
 const RES_X: u16 = 32;
 const RES_Y: u16 = 32;
 
-const UPDATES_PER_SECOND: f32 = 2.0;
+const UPDATES_PER_SECOND: f32 = 60.0;
 const MILLIS_PER_UPDATE: u64 = (1.0 / UPDATES_PER_SECOND * 1000.0) as u64;
 
-const NB_CHAR: usize = 2;
+/// The `char_id` of a tile nobody stands on
+const NO_CHAR: usize = std::usize::MAX;
+const NB_TEAM: usize = 2;
+
+/// Colors of each team, on the HUD and the spawn points
+const TEAM_COLORS: [[f32; 4]; NB_TEAM] = [[0.1, 0.3, 0.8, 0.8], [0.9, 0.6, 0.1, 0.8]];
+
+/// How many turns ahead the timeline shows
+const TIMELINE_LENGTH: usize = 8;
+
+/// Hit points of a wall, unless the map says otherwise
+const WALL_HP: u8 = 4;
+/// Damage dealt by a trap, and how many turns it takes to arm itself again
+const TRAP_DAMAGE: u8 = 1;
+const TRAP_REARM: u32 = 4;
+/// How much a trap pushes back the next turn of its victim
+const TRAP_DELAY: i32 = (TURN_LENGTH / 4) as i32;
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 struct GridPosition {
@@ -24,19 +93,14 @@ struct GridPosition {
 }
 
 impl GridPosition {
-    fn as_index(&self) -> usize {
-        (self.x + self.y * GRID_X) as usize
+    /// Index of the tile in a map `width` tiles wide
+    fn as_index(&self, width: u16) -> usize {
+        self.x as usize + self.y as usize * width as usize
     }
-}
 
-impl From<GridPosition> for graphics::Rect {
-    fn from(pos: GridPosition) -> Self {
-        graphics::Rect::new_i32(
-            pos.x as i32 * RES_X as i32,
-            pos.y as i32 * RES_Y as i32,
-            RES_X as i32,
-            RES_Y as i32,
-        )
+    /// Number of steps between two tiles, walls aside
+    fn distance(&self, other: GridPosition) -> u16 {
+        ((self.x as i32 - other.x as i32).abs() + (self.y as i32 - other.y as i32).abs()) as u16
     }
 }
 
@@ -48,75 +112,338 @@ impl From<(u16, u16)> for GridPosition {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 enum TileState {
     Empty,
     Wall,
     Trap,
+    /// Difficult ground, costs two mp to enter
+    Rough,
+    /// Only swimmers get in
+    Water,
+    /// High ground, attacks from there go further and hit harder
+    High,
+    /// Halves the damage of ranged attacks on whoever stands there
+    Cover,
+    Door,
+    OpenDoor,
+    /// What is left of a wall, as hard to walk on as difficult ground
+    Rubble,
+}
+
+impl TileState {
+    /// Every kind of tile, e.g. for the tileset to check it has them all
+    const ALL: [TileState; 10] = [
+        TileState::Empty,
+        TileState::Wall,
+        TileState::Trap,
+        TileState::Rough,
+        TileState::Water,
+        TileState::High,
+        TileState::Cover,
+        TileState::Door,
+        TileState::OpenDoor,
+        TileState::Rubble,
+    ];
+
+    /// Name of the tile in data files
+    fn key(&self) -> &'static str {
+        match *self {
+            TileState::Empty => "floor",
+            TileState::Wall => "wall",
+            TileState::Trap => "trap",
+            TileState::Rough => "rough",
+            TileState::Water => "water",
+            TileState::High => "high",
+            TileState::Cover => "cover",
+            TileState::Door => "door",
+            TileState::OpenDoor => "open_door",
+            TileState::Rubble => "rubble",
+        }
+    }
+
+    /// How the tile is written in map files
+    fn symbol(&self) -> char {
+        match *self {
+            TileState::Empty => '.',
+            TileState::Wall => '#',
+            TileState::Trap => '^',
+            TileState::Rough => ',',
+            TileState::Water => '~',
+            TileState::High => ':',
+            TileState::Cover => '%',
+            TileState::Door => '+',
+            TileState::OpenDoor => '/',
+            TileState::Rubble => '*',
+        }
+    }
+
+    fn from_symbol(symbol: char) -> Option<TileState> {
+        TileState::ALL.iter().find(|state| state.symbol() == symbol).cloned()
+    }
+
+    /// How many mp it takes to step in, if it can be walked on at all
+    fn cost(&self, swims: bool) -> Option<u8> {
+        match *self {
+            TileState::Wall | TileState::Door => None,
+            TileState::Water if !swims => None,
+            TileState::Rough | TileState::Rubble => Some(2),
+            _ => Some(1),
+        }
+    }
+
+    /// Whether ranged attacks go through
+    fn blocks_sight(&self) -> bool {
+        match *self {
+            TileState::Wall | TileState::Door => true,
+            _ => false,
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
 struct Tile {
     state: TileState,
     cooldown: u32,
+    /// Hit points left to a wall, walls at 0 cannot be broken
+    hp: u8,
     char_id: usize,
 }
 
 impl Tile {
+    /// A tile nobody stands on
+    fn new(state: TileState) -> Tile {
+        Tile {
+            state: state,
+            cooldown: 0,
+            hp: match state {
+                TileState::Wall => WALL_HP,
+                _ => 0,
+            },
+            char_id: NO_CHAR,
+        }
+    }
+
+    /// Whether anyone could stand here: nobody does and it is not blocked,
+    /// even for those who cannot swim. Traps don't count, walking into one is allowed.
     fn is_empty(&self) -> bool {
+        self.char_id == NO_CHAR && self.state.cost(false).is_some()
+    }
+
+    /// How many mp it takes to step in, if it is free and can be walked on
+    fn cost(&self, swims: bool) -> Option<u8> {
+        if self.char_id == NO_CHAR {
+            self.state.cost(swims)
+        } else {
+            None
+        }
+    }
+
+    /// What the tooltip says about this tile
+    fn describe(&self) -> String {
         match self.state {
-            // doesn't prevent the player from going into a trap
-            TileState::Wall => false,
-            _ => self.char_id == NB_CHAR + 1,
+            TileState::Empty => String::from("Floor"),
+            TileState::Wall if self.hp == 0 => String::from("Wall (unbreakable)"),
+            TileState::Wall => format!("Wall (HP {})", self.hp),
+            TileState::Trap if self.cooldown == 0 => String::from("Trap (armed)"),
+            TileState::Trap => format!("Trap (armed in {} turns)", self.cooldown),
+            TileState::Rough => String::from("Difficult ground (2 MP)"),
+            TileState::Water => String::from("Water (swimmers only)"),
+            TileState::High => String::from("High ground (+1 range and damage)"),
+            TileState::Cover => String::from("Cover (halves ranged damage)"),
+            TileState::Door => String::from("Closed door"),
+            TileState::OpenDoor => String::from("Open door"),
+            TileState::Rubble => String::from("Rubble (2 MP)"),
         }
     }
 
-    fn draw(&self, ctx: &mut Context, pos: GridPosition) -> GameResult<()> {
+    fn draw(&self, ctx: &mut Context, rect: graphics::Rect) -> GameResult<()> {
         let color = match self.state {
             TileState::Empty => [0.5, 0.3, 0.3, 1.0],
             TileState::Wall => [0.5, 0.0, 0.0, 1.0],
             TileState::Trap => [0.0, 0.6, 0.2, 1.0],
+            TileState::Rough => [0.45, 0.3, 0.2, 1.0],
+            TileState::Water => [0.1, 0.3, 0.7, 1.0],
+            TileState::High => [0.6, 0.5, 0.35, 1.0],
+            TileState::Cover => [0.15, 0.45, 0.15, 1.0],
+            TileState::Door => [0.3, 0.18, 0.1, 1.0],
+            TileState::OpenDoor => [0.55, 0.35, 0.2, 1.0],
+            TileState::Rubble => [0.4, 0.2, 0.2, 1.0],
         };
         graphics::set_color(ctx, color.into())?;
-        graphics::rectangle(ctx, graphics::DrawMode::Fill, pos.into())
+        graphics::rectangle(ctx, graphics::DrawMode::Fill, rect)
     }
 }
 
+#[derive(Debug, Clone)]
 struct Map {
-    tiles: [Tile; 16*16],
+    width: u16,
+    height: u16,
+    tiles: Vec<Tile>,
+    /// Where the characters of each team start: (team, position)
+    spawns: Vec<(usize, GridPosition)>,
+    /// Tiles changed by abilities, and what they go back to after a number of turns
+    temporary: Vec<(GridPosition, TileState, u32)>,
+    /// Items lying on the ground: (position, item key)
+    items: Vec<(GridPosition, String)>,
+    /// How to win on this map
+    objective: Objective,
 }
 
 impl Map {
-    pub fn new(arr: Tile) -> Self {
+    pub fn new(width: u16, height: u16, tile: Tile) -> Self {
         Map {
-            tiles: [arr; 16*16],
+            width: width,
+            height: height,
+            tiles: vec![tile; width as usize * height as usize],
+            spawns: Vec::new(),
+            temporary: Vec::new(),
+            items: Vec::new(),
+            objective: Objective::Eliminate,
         }
     }
 
-    fn draw(&self, ctx: &mut Context) -> GameResult<()> {
-        for x in 0..GRID_X {
-            for y in 0..GRID_Y {
-                self.tiles[(y * GRID_X + x) as usize].draw(ctx, GridPosition{x,y})?
+    /// Changes the size of the map, keeping the top left corner. New tiles are floor.
+    fn resize(&mut self, width: u16, height: u16) {
+        let mut tiles = vec![Tile::new(TileState::Empty); width as usize * height as usize];
+        for x in 0..width.min(self.width) {
+            for y in 0..height.min(self.height) {
+                let pos = GridPosition { x, y };
+                tiles[pos.as_index(width)] = *self.tile(pos);
+            }
+        }
+        self.width = width;
+        self.height = height;
+        self.tiles = tiles;
+        self.spawns.retain(|&(_, pos)| pos.x < width && pos.y < height);
+        self.items.retain(|&(pos, _)| pos.x < width && pos.y < height);
+        if let Some(pos) = self.objective.tile() {
+            if pos.x >= width || pos.y >= height {
+                self.objective = Objective::Eliminate;
             }
         }
+    }
+
+    /// Puts the characters on the spawn points of their team, in order. Those
+    /// without a spawn point left go to the first free tile.
+    fn place(&mut self, characters: &mut [Character]) -> GameResult<()> {
+        let mut used = vec![false; self.spawns.len()];
+        for character in characters.iter_mut().filter(|character| character.is_alive()) {
+            let spawn = (0..self.spawns.len()).find(|&i| {
+                let (team, pos) = self.spawns[i];
+                !used[i] && team == character.team && self.tile(pos).is_empty()
+            });
+            let pos = match spawn {
+                Some(i) => {
+                    used[i] = true;
+                    self.spawns[i].1
+                },
+                None => {
+                    let index = self.tiles.iter().position(|tile| tile.is_empty())
+                        .ok_or_else(|| GameError::ResourceLoadError(String::from("no room left on the map")))?;
+                    GridPosition { x: (index % self.width as usize) as u16, y: (index / self.width as usize) as u16 }
+                },
+            };
+            character.pos = pos;
+            character.selector = pos;
+            self.tile_mut(pos).char_id = character.id;
+        }
         Ok(())
     }
 
-    fn neighbour(&self, pos: GridPosition, dir: &Direction) -> Tile {
-        match dir {
-            Direction::Up => self.tiles[pos.as_index() - GRID_X as usize],
-            Direction::Down => self.tiles[pos.as_index() + GRID_X as usize],
-            Direction::Left => self.tiles[pos.as_index() - 1],
-            Direction::Right => self.tiles[pos.as_index() + 1]
+    fn tile(&self, pos: GridPosition) -> &Tile {
+        &self.tiles[pos.as_index(self.width)]
+    }
+
+    fn tile_mut(&mut self, pos: GridPosition) -> &mut Tile {
+        &mut self.tiles[pos.as_index(self.width)]
+    }
+
+    fn contains(&self, pos: GridPosition) -> bool {
+        pos.x < self.width && pos.y < self.height
+    }
+
+    /// Only draws the tiles the camera can see, maps can be big
+    fn draw(&self, ctx: &mut Context, camera: &Camera) -> GameResult<()> {
+        let (x0, y0, x1, y1) = camera.visible();
+        for x in x0..x1 {
+            for y in y0..y1 {
+                let pos = GridPosition{x,y};
+                self.tile(pos).draw(ctx, camera.rect(pos))?
+            }
         }
+        Ok(())
     }
 
-    fn is_available(&self, pos: GridPosition, dir: &Direction) -> bool {
-        self.neighbour(pos, dir).is_empty()
+    /// The position one step away from `pos`, if it is still on the map
+    fn step(&self, pos: GridPosition, dir: &Direction) -> Option<GridPosition> {
+        let (x, y) = (pos.x as i32, pos.y as i32);
+        let (x, y) = match dir {
+            Direction::Up => (x, y - 1),
+            Direction::Down => (x, y + 1),
+            Direction::Left => (x - 1, y),
+            Direction::Right => (x + 1, y),
+        };
+        if x >= 0 && y >= 0 && x < self.width as i32 && y < self.height as i32 {
+            Some(GridPosition { x: x as u16, y: y as u16 })
+        } else {
+            None
+        }
+    }
+
+    fn neighbour(&self, pos: GridPosition, dir: &Direction) -> Option<&Tile> {
+        self.step(pos, dir).map(|next| self.tile(next))
+    }
+
+    /// How many mp the step from `pos` towards `dir` costs, if it can be taken
+    fn move_cost(&self, pos: GridPosition, dir: &Direction, swims: bool) -> Option<u8> {
+        self.neighbour(pos, dir).and_then(|tile| tile.cost(swims))
+    }
+
+    fn is_available(&self, pos: GridPosition, dir: &Direction, swims: bool) -> bool {
+        self.move_cost(pos, dir, swims).is_some()
+    }
+
+    /// A turn went by: traps get closer to being armed again, and what the
+    /// abilities made goes away when its time is up. Returns the tiles that changed.
+    fn tick(&mut self) -> Vec<GridPosition> {
+        let mut changed = Vec::new();
+        for (index, tile) in self.tiles.iter_mut().enumerate() {
+            if tile.state == TileState::Trap && tile.cooldown > 0 {
+                tile.cooldown -= 1;
+                changed.push(GridPosition { x: (index % self.width as usize) as u16, y: (index / self.width as usize) as u16 });
+            }
+        }
+        for (pos, state, turns) in std::mem::replace(&mut self.temporary, Vec::new()) {
+            if turns > 1 {
+                self.temporary.push((pos, state, turns - 1));
+                continue;
+            }
+            // whoever stands on an expiring trap stays there
+            let char_id = self.tile(pos).char_id;
+            *self.tile_mut(pos) = Tile { char_id: char_id, ..Tile::new(state) };
+            changed.push(pos);
+        }
+        changed
+    }
+
+    /// Whether nothing stands between `from` and `to` that blocks sight, both
+    /// ends aside. The line goes from the middle of a tile to the other.
+    fn line_of_sight(&self, from: GridPosition, to: GridPosition) -> bool {
+        let (dx, dy) = (to.x as f32 - from.x as f32, to.y as f32 - from.y as f32);
+        let steps = (dx.abs().max(dy.abs()) * 2.0) as u32;
+        (1..steps).all(|i| {
+            let t = i as f32 / steps as f32;
+            let pos = GridPosition {
+                x: (from.x as f32 + 0.5 + dx * t) as u16,
+                y: (from.y as f32 + 0.5 + dy * t) as u16,
+            };
+            pos == from || pos == to || !self.tile(pos).state.blocks_sight()
+        })
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 enum Direction {
     Up,
     Down,
@@ -134,6 +461,16 @@ impl Direction {
             _ => None,
         }
     }
+
+    pub fn from_button(btn: Button) -> Option<Direction> {
+        match btn {
+            Button::DPadUp => Some(Direction::Up),
+            Button::DPadDown => Some(Direction::Down),
+            Button::DPadLeft => Some(Direction::Left),
+            Button::DPadRight => Some(Direction::Right),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -142,152 +479,771 @@ enum Status {
     Dead,
 }
 
+#[derive(Debug, Copy, Clone)]
+enum Class {
+    Fighter,
+    Archer,
+}
+
+impl Class {
+    /// Name of the class in data files
+    fn key(&self) -> &'static str {
+        match *self {
+            Class::Fighter => "fighter",
+            Class::Archer => "archer",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Class> {
+        match key {
+            "fighter" => Some(Class::Fighter),
+            "archer" => Some(Class::Archer),
+            _ => None,
+        }
+    }
+
+    /// Damage dealt by an attack
+    fn attack(&self) -> u8 {
+        match *self {
+            Class::Fighter => 2,
+            Class::Archer => 1,
+        }
+    }
+
+    /// How many tiles away an attack reaches
+    fn range(&self) -> u16 {
+        match *self {
+            Class::Fighter => 1,
+            Class::Archer => 3,
+        }
+    }
+
+    fn ability(&self) -> Ability {
+        match *self {
+            Class::Fighter => Ability::RaiseWall,
+            Class::Archer => Ability::SetTrap,
+        }
+    }
+
+    /// How often they play, see `Timeline`
+    fn speed(&self) -> u32 {
+        match *self {
+            Class::Fighter => 4,
+            Class::Archer => 5,
+        }
+    }
+
+    /// Whether water lets them through
+    fn swims(&self) -> bool {
+        match *self {
+            Class::Fighter => true,
+            // better keep the bowstrings dry
+            Class::Archer => false,
+        }
+    }
+}
+
 #[derive(Debug)]
 struct Character {
     id: usize,
+    /// Its unit in the roster, none for the enemies of a scenario
+    unit: Option<usize>,
+    name: String,
+    team: usize,
+    class: Class,
+    level: u32,
+    /// Damage added to the attacks of its class, grown with its levels
+    attack: u8,
     pos: GridPosition,
     mobi: u8,
     mp: u8,
     facing: Direction,
     hp: u8,
+    max_hp: u8,
     state: Status,
     selector: GridPosition,
+    /// Own turns left before the ability can be used again
+    recharge: u8,
+    /// Whether it already played this phase, in phases mode
+    acted: bool,
+    /// Keys of the items it carries, and which one is selected
+    inventory: Vec<String>,
+    item: usize,
 }
 
 impl Character {
-    fn draw_selector(&self, ctx: &mut Context) -> GameResult<()> {
+    /// What the tooltip says about this character
+    fn describe(&self) -> String {
+        let ability = match self.recharge {
+            0 => String::from("ready"),
+            turns => format!("in {} turns", turns),
+        };
+        format!("{} (team {}, level {} {}) HP {}/{} MP {}/{} speed {}, {} {}", self.name, self.team, self.level, self.class.key(),
+                self.hp, self.max_hp, self.mp, self.mobi, self.class.speed(), self.class.ability().name(), ability)
+    }
+
+    /// Damage dealt by its attacks
+    fn damage(&self) -> u8 {
+        self.class.attack() + self.attack
+    }
+
+    fn is_alive(&self) -> bool {
+        match self.state {
+            Status::Alive => true,
+            Status::Dead => false,
+        }
+    }
+
+    fn draw_selector(&self, ctx: &mut Context, camera: &Camera) -> GameResult<()> {
         graphics::set_color(ctx, [0.2, 0.3, 0.8, 0.4].into())?;
-        graphics::rectangle(ctx, graphics::DrawMode::Fill, self.selector.into())
+        graphics::rectangle(ctx, graphics::DrawMode::Fill, camera.rect(self.selector))
     }
 
-    fn draw(&self, ctx: &mut Context) -> GameResult<()> {
-        // Character color. Let's go with a cool blue (RGBA)
-        graphics::set_color(ctx, [0.1, 0.3, 0.8, 1.0].into())?;
+    fn draw(&self, ctx: &mut Context, rect: graphics::Rect, tint: Option<graphics::Color>) -> GameResult<()> {
+        // Character color: the one of its team (RGBA), unless it is flashing
+        let team = TEAM_COLORS[self.team];
+        graphics::set_color(ctx, tint.unwrap_or([team[0], team[1], team[2], 1.0].into()))?;
         // Then we draw a triangle – which orientation depends on the character's – with the Fill draw mode.
         let points = match self.facing {
             Direction::Up => [
-                graphics::Point2::new((self.pos.x * RES_X) as f32 + 0.0, (self.pos.y * RES_Y) as f32 + 32.0),
-                graphics::Point2::new((self.pos.x * RES_X) as f32 + 32.0, (self.pos.y * RES_Y) as f32 + 32.0),
-                graphics::Point2::new((self.pos.x * RES_X) as f32 + 16.0, (self.pos.y * RES_Y) as f32 + 0.0)
+                graphics::Point2::new(rect.left(), rect.bottom()),
+                graphics::Point2::new(rect.right(), rect.bottom()),
+                graphics::Point2::new(rect.x + rect.w / 2.0, rect.top())
             ],
             Direction::Down => [
-                graphics::Point2::new((self.pos.x * RES_X) as f32 + 0.0, (self.pos.y * RES_Y) as f32 + 0.0),
-                graphics::Point2::new((self.pos.x * RES_X) as f32 + 32.0, (self.pos.y * RES_Y) as f32 + 0.0),
-                graphics::Point2::new((self.pos.x * RES_X) as f32 + 16.0, (self.pos.y * RES_Y) as f32 + 32.0)
+                graphics::Point2::new(rect.left(), rect.top()),
+                graphics::Point2::new(rect.right(), rect.top()),
+                graphics::Point2::new(rect.x + rect.w / 2.0, rect.bottom())
             ],
             Direction::Left => [
-                graphics::Point2::new((self.pos.x * RES_X) as f32 + 32.0, (self.pos.y * RES_Y) as f32 + 0.0),
-                graphics::Point2::new((self.pos.x * RES_X) as f32 + 32.0, (self.pos.y * RES_Y) as f32 + 32.0),
-                graphics::Point2::new((self.pos.x * RES_X) as f32 + 0.0, (self.pos.y * RES_Y) as f32 + 16.0)
+                graphics::Point2::new(rect.right(), rect.top()),
+                graphics::Point2::new(rect.right(), rect.bottom()),
+                graphics::Point2::new(rect.left(), rect.y + rect.h / 2.0)
             ],
             Direction::Right => [
-                graphics::Point2::new((self.pos.x * RES_X) as f32 + 0.0, (self.pos.y * RES_Y) as f32 + 0.0),
-                graphics::Point2::new((self.pos.x * RES_X) as f32 + 0.0, (self.pos.y * RES_Y) as f32 + 32.0),
-                graphics::Point2::new((self.pos.x * RES_X) as f32 + 32.0, (self.pos.y * RES_Y) as f32 + 16.0)
+                graphics::Point2::new(rect.left(), rect.top()),
+                graphics::Point2::new(rect.left(), rect.bottom()),
+                graphics::Point2::new(rect.right(), rect.y + rect.h / 2.0)
             ],
         };
         graphics::polygon(ctx, graphics::DrawMode::Fill, &points)
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 enum Action {
     Attack,
     Move,
 }
+
+/// Something that happened in the rules. The rules only push them, the
+/// animations play them afterwards.
+#[derive(Debug, Copy, Clone)]
+enum Event {
+    Moved { id: usize, from: GridPosition, to: GridPosition },
+    Attacked { id: usize, target: GridPosition },
+    Hit { id: usize, damage: u8 },
+    Healed { id: usize },
+    Died { id: usize },
+    TurnStarted { id: usize },
+    /// The tile changed state, e.g. a door was opened
+    TileChanged { pos: GridPosition },
+}
+
 /// Now we have the heart of our game, the GameState. This struct
 /// will implement ggez's `EventHandler` trait and will therefore drive
 /// everything else that happens in our game.
 struct GameState {
     /// First we need a character
-    characters: [Character;2],
+    characters: Vec<Character>,
     action: Action,
     ap: u32,
     /// Then a map
     map: Map,
-    /// Whether the game is over or not
+    /// Whether the game is over or not, and how it ended
     gameover: bool,
+    outcome: Option<Outcome>,
+    /// Team on the capture tile and for how many turns, if any
+    held: Option<(usize, u32)>,
+    /// What each character did so far, for the results screen
+    records: Vec<Record>,
+    /// Who fights, as they grew over the matches, and how they grew in this one
+    roster: Roster,
+    progress: Vec<String>,
+    /// The scenarios, if the campaign could be loaded
+    campaign: Option<Campaign>,
+    /// What the triggers of the scenario said so far
+    messages: Vec<String>,
+    /// The map as it was when the game started, to play again
+    start: Map,
+    /// The map of the command line, for a new game from the title
+    home: Map,
     turn: u32,
+    /// The selected character: whose turn it is, or in phases mode the one
+    /// the team in play picked. Then who plays next.
     char_id: usize,
+    turn_mode: TurnMode,
+    timeline: Timeline,
+    /// Time limits, if any
+    clock: Clock,
+    /// What the items lying around and in the bags do
+    items: Items,
+    /// Who is allowed to send commands from which gamepad
+    controls: Controls,
+    /// The AI playing each team, none for a player, and the seed of the
+    /// random ones
+    ai: Vec<Option<Box<dyn Controller>>>,
+    seed: u64,
+    /// The commands played since the start, to save the game, and those of
+    /// a replay still to be shown
+    history: Vec<Command>,
+    replay: VecDeque<Command>,
+    /// Whether the debug overlay is shown over the board
+    overlay: bool,
+    /// The tile under the mouse, if any, and the font of its tooltip
+    hovered: Option<GridPosition>,
+    font: graphics::Font,
+    /// Which part of the map we see
+    camera: Camera,
+    /// Sprites of the board, if the tileset could be loaded
+    tileset: Option<Tileset>,
+    /// What the rules did since the last update, and what is still being shown
+    events: Vec<Event>,
+    animations: Animations,
+    /// The map editor, kept while its map is playtested
+    editor: Option<Editor>,
+    editing: bool,
+    /// Whether the map played is from the editor, only being tried out
+    playtest: bool,
+    /// Played by the simulation, without a window nor saves
+    headless: bool,
+    /// Where our files are
+    resources: PathBuf,
     /// And we track the last time we updated so that we can limit
     /// our update rate.
     last_update: Instant,
 }
 
 impl GameState {
-    /// Our new function will set up the initial state of our game.
-    pub fn new() -> Self {
-        // First we put our snake a quarter of the way across our grid in the x axis
-        // and half way down the y axis. This works well since we start out moving to the right.
-        // Then we choose a random place to put our piece of food using the helper we made
-        // earlier.
-        // let food_pos = GridPosition::random(GRID_SIZE.0, GRID_SIZE.1);
-        let bitmap = [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
-                      1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1,
-                      1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1,
-                      1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1,
-                      1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 1, 0, 2, 2, 1,
-                      1, 0, 0, 0, 0, 1, 0, 1, 0, 1, 1, 1, 2, 2, 0, 1,
-                      1, 0, 0, 0, 0, 1, 1, 1, 0, 0, 0, 1, 0, 0, 0, 1,
-                      1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 0, 1,
-                      1, 0, 1, 1, 0, 0, 0, 1, 1, 1, 2, 2, 1, 0, 0, 1,
-                      1, 0, 0, 1, 0, 1, 0, 1, 1, 1, 0, 0, 0, 0, 0, 1,
-                      1, 0, 0, 1, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1,
-                      1, 0, 1, 1, 0, 1, 0, 0, 0, 0, 0, 0, 1, 0, 0, 1,
-                      1, 0, 0, 0, 0, 1, 1, 1, 1, 1, 0, 0, 1, 0, 0, 1,
-                      1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 1, 0, 0, 1,
-                      1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1,
-                      1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1];
-        let mut map = Map::new(Tile{state: TileState::Empty, cooldown: 0, char_id: NB_CHAR + 1});
-        // map.tiles[10].state = TileState::Wall;
-        // map.tiles[11].state = TileState::Wall;
-        // map.tiles[19].state = TileState::Wall;
-        // map.tiles[20].state = TileState::Wall;
-        // map.tiles[22].state = TileState::Wall;
-        // map.tiles[34].state = TileState::Wall;
-        // map.tiles[42].state = TileState::Wall;
-        // map.tiles[50].state = TileState::Wall;
-        // for index in 0..16 {
-        //     map.tiles[index].state = TileState::Wall;
-        //     map.tiles[(GRID_X * GRID_Y - 1) as usize - index].state = TileState::Wall;
-        //     map.tiles[index * GRID_X as usize].state = TileState::Wall;
-        //     map.tiles[index * GRID_X as usize + GRID_X as usize - 1].state = TileState::Wall;
-        // }
-        for (index,bit) in bitmap.iter().enumerate() {
-            match bit {
-                2 => map.tiles[index].state = TileState::Trap,
-                1 => map.tiles[index].state = TileState::Wall,
-                _ => (),
-            }
-        }
-
-        GameState {
-            characters: [Character {
-                             id: 0,
-                             pos: GridPosition{x:1,y:1},
-                             mobi: 3,
-                             mp: 3,
-                             facing: Direction::Down,
-                             selector: GridPosition{x:1,y:1},
-                             hp: 5,
-                             state: Status::Alive},
-                         Character {
-                             id: 1,
-                             pos: GridPosition{x:13,y:13},
-                             mobi: 3,
-                             mp: 3,
-                             facing: Direction::Up,
-                             selector: GridPosition{x:13,y:13},
-                             hp: 5,
-                             state: Status::Alive}],
+    /// Our new function will set up the initial state of our game on `map`.
+    pub fn new(map: Map) -> GameResult<Self> {
+        let camera = Camera::new((GRID_X * RES_X) as f32, (GRID_Y * RES_Y) as f32, &map);
+        let mut state = GameState {
+            characters: Vec::new(),
             action: Action::Move,
             ap: 3,
-            map: map,
+            map: Map::new(0, 0, Tile::new(TileState::Empty)),
             gameover: false,
+            outcome: None,
+            held: None,
+            records: Vec::new(),
+            roster: Roster::parse(roster::DEFAULT_ROSTER)?,
+            progress: Vec::new(),
+            campaign: None,
+            messages: Vec::new(),
+            start: Map::new(0, 0, Tile::new(TileState::Empty)),
+            home: map.clone(),
             char_id: 0,
+            turn_mode: TurnMode::Initiative,
+            timeline: Timeline::new(&[]),
             turn: 0,
+            clock: Clock::new(None, None),
+            items: Items::parse(item::DEFAULT_ITEMS)?,
+            controls: Controls::new(),
+            ai: (0..NB_TEAM).map(|_| None).collect(),
+            seed: 0,
+            history: Vec::new(),
+            replay: VecDeque::new(),
+            overlay: false,
+            hovered: None,
+            font: graphics::Font::default_font()?,
+            camera: camera,
+            tileset: None,
+            events: Vec::new(),
+            animations: Animations::new(),
+            editor: None,
+            editing: false,
+            playtest: false,
+            headless: false,
+            resources: PathBuf::from("resources"),
             last_update: Instant::now(),
+        };
+        state.restart(map)?;
+        Ok(state)
+    }
+
+    /// Starts a new game on `map`, keeping the settings and the assets.
+    fn restart(&mut self, mut map: Map) -> GameResult<()> {
+        let mut characters = self.roster.characters(self.campaign.as_ref().and_then(|campaign| campaign.enemies()));
+        if let Some(team) = (0..NB_TEAM).find(|&team| !characters.iter().any(|character| character.team == team)) {
+            return Err(GameError::ResourceLoadError(format!("team {} has nobody to play", team)));
+        }
+        if let Objective::Assassinate { leader } = map.objective {
+            if leader >= characters.len() {
+                return Err(GameError::ResourceLoadError(format!("the leader to assassinate, character {}, is not there", leader)));
+            }
+        }
+        let items = &self.items;
+        map.items.retain(|&(pos, ref key)| {
+            let known = items.get(key).is_some();
+            if !known {
+                warn!("Unknown item {} at ({}, {}), left out", key, pos.x, pos.y);
+            }
+            known
+        });
+        self.start = map.clone();
+        map.place(&mut characters)?;
+        self.characters = characters;
+        self.map = map;
+        self.action = Action::Move;
+        self.gameover = false;
+        self.outcome = None;
+        self.held = None;
+        self.records = vec![Record::default(); self.characters.len()];
+        self.progress.clear();
+        self.clock.restart();
+        self.timeline = Timeline::new(&self.characters);
+        self.char_id = self.first_actor().unwrap_or(0);
+        self.turn = 0;
+        self.events.clear();
+        self.history.clear();
+        self.replay.clear();
+        self.playtest = false;
+        // it was over the last map, the mouse tells again when it moves
+        self.hovered = None;
+        self.animations = Animations::new();
+        self.camera.set_map_size(&self.map);
+        self.camera.snap_to(self.characters[self.char_id].pos);
+        self.messages.clear();
+        if let Some(triggers) = self.campaign.as_mut().and_then(|campaign| campaign.triggers_mut()) {
+            triggers.rearm();
+        }
+        let first = self.char_id;
+        self.run_hooks(Trigger::TurnStart, first);
+        Ok(())
+    }
+
+    /// Loads what needs a context to be drawn, and remembers where our files
+    /// are. Missing sprites are not an error, we just keep drawing plain shapes.
+    pub fn load_assets(&mut self, ctx: &mut Context, resources: &Path) {
+        self.resources = resources.to_path_buf();
+        match Tileset::load(ctx, resources) {
+            Ok(tileset) => self.tileset = Some(tileset),
+            Err(e) => warn!("No tileset, drawing shapes instead: {}", e),
+        }
+        match Items::load(resources.join("items.txt")) {
+            Ok(items) => self.items = items,
+            Err(e) => warn!("Using the built-in items: {}", e),
+        }
+        // the saved roster if there is one, else the one we start with
+        let save = resources.join(roster::SAVE);
+        let path = if save.exists() { save } else { resources.join("roster.txt") };
+        match Roster::load(&path) {
+            Ok(roster) => self.roster = roster,
+            Err(e) => warn!("Using the built-in roster: {}", e),
+        }
+        match Campaign::load(resources.join("campaign.txt")) {
+            Ok(mut campaign) => {
+                let save = resources.join(campaign::SAVE);
+                if save.exists() {
+                    if let Err(e) = campaign.load_progress(&save) {
+                        warn!("Starting the campaign over: {}", e);
+                    }
+                }
+                self.campaign = Some(campaign);
+            },
+            Err(e) => warn!("No campaign: {}", e),
+        }
+    }
+
+    /// Draws the board, the characters and the HUD of a game
+    fn draw_game(&mut self, ctx: &mut Context) -> GameResult<()> {
+        match self.tileset {
+            // All the sprites at once
+            Some(ref mut tileset) => tileset.draw(ctx, &self.map, &self.characters, &self.animations, &self.camera)?,
+            None => {
+                // Draws the tiles
+                self.map.draw(ctx, &self.camera)?;
+                // Then we tell the characters to draw themselves where the animations say
+                for character in self.characters.iter() {
+                    if self.animations.shown(character) {
+                        let pos = self.animations.position(character);
+                        character.draw(ctx, self.camera.rect_at(pos.x, pos.y), self.animations.tint(character.id))?;
+                    }
+                }
+            },
+        }
+        self.draw_items(ctx, &self.camera)?;
+        if let Some(pos) = self.map.objective.tile() {
+            let rect = self.camera.rect(pos);
+            graphics::set_color(ctx, [1.0, 0.85, 0.1, 1.0].into())?;
+            graphics::rectangle(ctx, graphics::DrawMode::Line(rect.w / 12.0), rect)?;
+        }
+        match self.action {
+            Action::Attack => self.characters[self.char_id].draw_selector(ctx, &self.camera)?,
+            _ => ()
+        }
+        // The board is done, the HUD can go over the black bars
+        self.camera.draw_letterbox(ctx)?;
+        let padding = self.camera.tile_width() / 8.0;
+        let mut lines = self.objective_lines();
+        lines.extend(self.clock.lines());
+        lines.extend(self.inventory_lines());
+        let said = self.messages.len().saturating_sub(triggers::MESSAGES_SHOWN);
+        lines.extend(self.messages[said..].iter().cloned());
+        hud::draw_panel(ctx, &self.font, &lines, 0.0, 0.0, padding)?;
+        let timeline: Vec<(String, graphics::Color)> = self.upcoming(TIMELINE_LENGTH).iter()
+            .map(|&id| {
+                let character = &self.characters[id];
+                (format!("{}{}", &character.class.key()[..1].to_uppercase(), id), TEAM_COLORS[character.team].into())
+            })
+            .collect();
+        hud::draw_timeline(ctx, &self.font, &timeline, self.camera.window(), self.camera.tile_width())?;
+        if self.overlay {
+            self.draw_overlay(ctx)?;
+        }
+        if let Some(lines) = self.campaign.as_ref().and_then(|campaign| campaign.lines()) {
+            return hud::draw_centered(ctx, &self.font, &lines, self.camera.window(), padding);
+        }
+        // the results are a scene of their own
+        if self.gameover {
+            return Ok(());
+        }
+        match self.hovered {
+            Some(pos) if self.map.contains(pos) => {
+                let mut lines = vec![format!("({}, {})", pos.x, pos.y)];
+                let tile = self.map.tile(pos);
+                lines.push(tile.describe());
+                if tile.char_id != NO_CHAR {
+                    lines.push(self.characters[tile.char_id].describe());
+                }
+                for &(_, ref key) in self.map.items.iter().filter(|item| item.0 == pos) {
+                    lines.push(self.items.name(key).to_string());
+                }
+                hud::draw_tooltip(ctx, &self.font, &lines, self.camera.rect(pos), self.camera.window())?;
+            },
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Goes from the editor to a playtest of the edited map, and back.
+    fn toggle_editor(&mut self) {
+        if self.editing {
+            let map = match self.editor {
+                Some(ref editor) => editor.map.clone(),
+                None => return,
+            };
+            if !map.is_connected() {
+                warn!("Careful, some spawn points cannot reach the others");
+            }
+            // the edited map is tried out with the roster, out of the campaign
+            if let Some(ref mut campaign) = self.campaign {
+                campaign.stop();
+            }
+            match self.restart(map) {
+                Ok(()) => {
+                    self.editing = false;
+                    self.playtest = true;
+                },
+                Err(e) => error!("Cannot playtest this map: {}", e),
+            }
+        } else {
+            if self.editor.is_none() {
+                let path = self.resources.join("maps").join("custom.txt");
+                self.editor = Some(Editor::new(&self.start, path));
+            }
+            if let Some(ref editor) = self.editor {
+                self.camera.set_map_size(&editor.map);
+            }
+            self.editing = true;
+        }
+    }
+
+    /// Once a game is over: the mission select in a campaign, the same map again otherwise
+    fn play_again(&mut self) {
+        if self.campaign.as_ref().map_or(false, |campaign| campaign.is_playing()) {
+            self.open_missions();
+            return;
+        }
+        if let Err(e) = self.restart_same() {
+            error!("Cannot play again: {}", e);
+        }
+    }
+
+    /// Starts over on the map the game started on, still a playtest if it
+    /// was one
+    fn restart_same(&mut self) -> GameResult<()> {
+        let (map, playtest) = (self.start.clone(), self.playtest);
+        self.restart(map)?;
+        self.playtest = playtest;
+        Ok(())
+    }
+
+    /// Whether a campaign screen is shown over the board
+    fn campaign_shown(&self) -> bool {
+        self.campaign.as_ref().map_or(false, |campaign| campaign.lines().is_some())
+    }
+
+    /// Whether the commands of the players count: not while a replay is
+    /// shown or an AI plays, the campaign screens aside.
+    fn takes_input(&self) -> bool {
+        let team = self.characters[self.char_id].team;
+        self.campaign_shown() || (self.replay.is_empty() && self.ai[team].is_none())
+    }
+
+    /// The game stood still under a menu, time starts again from now
+    fn resume(&mut self) {
+        self.last_update = Instant::now();
+    }
+
+    /// Plays a command for the current character, whatever device it came
+    /// from. Debug builds then check the game still holds together.
+    fn apply(&mut self, command: Command) {
+        self.play_command(command);
+        if cfg!(debug_assertions) {
+            self.check_consistency(command);
         }
     }
+
+    fn play_command(&mut self, command: Command) {
+        trace!("{:?} for character {} ({:?})", command, self.char_id, self.action);
+        if self.campaign_command(&command) {
+            return;
+        }
+        // wait for the animations to catch up with the rules
+        if self.animations.busy() || !self.events.is_empty() {
+            return;
+        }
+        if self.gameover {
+            return;
+        }
+        self.history.push(command);
+        let mut moved = false;
+        let character = &mut self.characters[self.char_id];
+        if character.mp == 0 {
+            self.action = Action::Attack;
+            character.selector = character.pos;
+            character.mp = character.mobi
+        }
+        match self.action {
+            Action::Move => {
+                match command {
+                    Command::Direction(dir) => {
+                        match self.map.move_cost(character.pos, &dir, character.class.swims()) {
+                            Some(cost) if cost <= character.mp => {
+                                let from = character.pos;
+                                self.map.tile_mut(character.pos).char_id = NO_CHAR;
+                                character.pos = self.map.step(character.pos, &dir).unwrap();
+                                self.map.tile_mut(character.pos).char_id = self.char_id;
+                                character.mp -= cost;
+                                debug!("{} moves from ({}, {}) to ({}, {}), {} MP left", character.name, from.x, from.y,
+                                       character.pos.x, character.pos.y, character.mp);
+                                self.events.push(Event::Moved { id: self.char_id, from: from, to: character.pos });
+                                moved = true;
+                            },
+                            _ => (),
+                        }
+                        character.facing = dir;
+                    },
+                    Command::Interact => {
+                        // opening or closing a door takes a step
+                        if let Some(pos) = self.map.step(character.pos, &character.facing) {
+                            let tile = self.map.tile_mut(pos);
+                            let state = match tile.state {
+                                TileState::Door => Some(TileState::OpenDoor),
+                                // not on someone's head
+                                TileState::OpenDoor if tile.char_id == NO_CHAR => Some(TileState::Door),
+                                _ => None,
+                            };
+                            if let Some(state) = state {
+                                tile.state = state;
+                                character.mp -= 1;
+                                self.events.push(Event::TileChanged { pos: pos });
+                            }
+                        }
+                    },
+                    Command::Confirm => {
+                        self.action = Action::Attack;
+                        character.selector = character.pos;
+                        character.mp = character.mobi
+                    },
+                    Command::EndTurn => self.end_turn(),
+                    Command::NextUnit => self.cycle_unit(),
+                    Command::EndPhase => self.end_phase(),
+                    Command::Select(pos) => {
+                        // picking another character of the team, in phases mode
+                        let id = self.map.tile(pos).char_id;
+                        if id != NO_CHAR {
+                            self.select_unit(id);
+                        }
+                    },
+                    Command::NextItem => self.cycle_item(),
+                    Command::Cancel | Command::Ability | Command::UseItem => (),
+                }
+            },
+            Action::Attack => {
+                match command {
+                    Command::Direction(dir) => {
+                        // the selector stays on the map
+                        if let Some(pos) = self.map.step(character.selector, &dir) {
+                            character.selector = pos;
+                        }
+                    },
+                    Command::Select(pos) => character.selector = pos,
+                    // put the selector back on the character
+                    Command::Cancel => character.selector = character.pos,
+                    Command::Confirm => {
+                        self.attack();
+                        self.end_turn()
+                    },
+                    // the ability replaces the attack
+                    Command::Ability => {
+                        if self.use_ability() {
+                            self.end_turn()
+                        }
+                    },
+                    Command::NextItem => self.cycle_item(),
+                    // the item replaces the attack too
+                    Command::UseItem => {
+                        if self.use_item() {
+                            self.end_turn()
+                        }
+                    },
+                    Command::Interact | Command::NextUnit => (),
+                    Command::EndTurn => self.end_turn(),
+                    Command::EndPhase => self.end_phase(),
+                }
+            },
+        }
+        if moved {
+            let id = self.char_id;
+            self.pick_up(id);
+            self.run_hooks(Trigger::TileEntered, id);
+            self.spring_trap(id);
+            if !self.characters[id].is_alive() {
+                self.end_turn();
+            }
+        }
+        self.check_victory();
+    }
+
+    /// The current character attacks the tile under its selector. Leaving the
+    /// selector on the character means no attack.
+    fn attack(&mut self) {
+        let (id, pos, target, class, damage) = {
+            let character = &self.characters[self.char_id];
+            (character.id, character.pos, character.selector, character.class, character.damage())
+        };
+        if target == pos {
+            return;
+        }
+        // high ground helps, cover protects from what comes from afar
+        let high = if self.map.tile(pos).state == TileState::High { 1 } else { 0 };
+        let distance = pos.distance(target);
+        if distance > class.range() + high as u16 || !self.map.line_of_sight(pos, target) {
+            debug!("Character {} cannot reach ({}, {}): distance {}, range {}", id, target.x, target.y, distance, class.range() + high as u16);
+            return;
+        }
+        self.events.push(Event::Attacked { id: id, target: target });
+        let mut damage = damage + high;
+        let victim_id = self.map.tile(target).char_id;
+        if victim_id != NO_CHAR {
+            if distance > 1 && self.map.tile(target).state == TileState::Cover {
+                damage /= 2;
+            }
+            self.hurt(victim_id, damage, Some(id));
+        } else {
+            self.break_wall(target, damage);
+        }
+    }
+
+    /// Takes hit points from a character, who dies at 0. `by` is the
+    /// attacker, if it was not the terrain.
+    fn hurt(&mut self, id: usize, damage: u8, by: Option<usize>) {
+        let victim = &mut self.characters[id];
+        let damage = damage.min(victim.hp);
+        victim.hp -= damage;
+        debug!("{} takes {} damage, {} HP left", victim.name, damage, victim.hp);
+        self.records[id].damage_taken += damage as u32;
+        if let Some(by) = by {
+            self.records[by].damage_dealt += damage as u32;
+        }
+        self.events.push(Event::Hit { id: id, damage: damage });
+        if victim.hp == 0 {
+            victim.state = Status::Dead;
+            self.map.tile_mut(victim.pos).char_id = NO_CHAR;
+            self.events.push(Event::Died { id: id });
+            if let Some(by) = by {
+                self.records[by].kills += 1;
+            }
+        } else {
+            return;
+        }
+        self.run_hooks(Trigger::Death, id);
+    }
+
+    /// Takes hit points from a wall, which turns to rubble at 0. Walls
+    /// without hit points to begin with cannot be broken.
+    fn break_wall(&mut self, pos: GridPosition, damage: u8) {
+        {
+            let tile = self.map.tile_mut(pos);
+            if tile.state != TileState::Wall || tile.hp == 0 {
+                return;
+            }
+            tile.hp = tile.hp.saturating_sub(damage);
+            if tile.hp == 0 {
+                tile.state = TileState::Rubble;
+            }
+        }
+        self.events.push(Event::TileChanged { pos: pos });
+    }
+
+    /// Springs the trap a character just stepped on, if it is armed
+    fn spring_trap(&mut self, id: usize) {
+        let pos = self.characters[id].pos;
+        {
+            let tile = self.map.tile_mut(pos);
+            if tile.state != TileState::Trap || tile.cooldown > 0 {
+                return;
+            }
+            tile.cooldown = TRAP_REARM;
+        }
+        debug!("Character {} springs the trap at ({}, {})", id, pos.x, pos.y);
+        self.events.push(Event::TileChanged { pos: pos });
+        self.run_hooks(Trigger::TrapTriggered, id);
+        self.hurt(id, TRAP_DAMAGE, None);
+        self.timeline.delay(id, TRAP_DELAY, &self.characters);
+    }
+
+    /// Ends the turn of the current character and change the state of the game
+    fn end_turn(&mut self) {
+        // waiting without doing anything brings the next turn closer
+        let waited = match self.action {
+            Action::Move => self.characters[self.char_id].mp == self.characters[self.char_id].mobi,
+            Action::Attack => false,
+        };
+        self.timeline.finish_turn(&self.characters[self.char_id], if waited { 0.5 } else { 1.0 });
+        let character = &mut self.characters[self.char_id];
+        character.acted = true;
+        character.mp = character.mobi;
+        character.recharge = character.recharge.saturating_sub(1);
+        self.turn += 1;
+        self.clock.next_turn();
+        self.count_hold();
+        for pos in self.map.tick() {
+            self.events.push(Event::TileChanged { pos: pos });
+        }
+        self.action = Action::Move;
+        // the dead don't play
+        if let Some(id) = self.next_actor() {
+            self.char_id = id;
+        }
+        debug!("Turn {}: character {} plays", self.turn, self.char_id);
+        self.events.push(Event::TurnStarted { id: self.char_id });
+        let id = self.char_id;
+        self.run_hooks(Trigger::TurnStart, id);
+    }
 }
 
 /// Now we implement EventHandler for GameState. This provides an interface
@@ -305,7 +1261,8 @@ impl event::EventHandler for GameState {
         // Ok(())
         // First we check to see if enough time has elapsed since our last update based on
         // the update rate we defined at the top.
-        if Instant::now() - self.last_update >= Duration::from_millis(MILLIS_PER_UPDATE) {
+        let now = Instant::now();
+        if now - self.last_update >= Duration::from_millis(MILLIS_PER_UPDATE) {
             // Then we check to see if the game is over. If not, we'll update. If so, we'll just do nothing.
             // if !self.gameover {
             //     // Here we do the actual updating of our game world. First we tell the snake to update itself,
@@ -328,33 +1285,55 @@ impl event::EventHandler for GameState {
             //         // }
             //     }
             // }
+            // The animations move on, starting with what the rules just did
+            for event in self.events.drain(..) {
+                self.animations.push(event);
+            }
+            // the view follows whoever starts playing
+            if let Some(&Event::TurnStarted { id }) = self.animations.current() {
+                self.camera.center_on(self.characters[id].pos);
+            }
+            self.animations.tick();
+            self.camera.update();
+            // the replay and the AIs play, and the clock only runs while someone can
+            if !self.editing && !self.gameover && !self.campaign_shown() && !self.animations.busy() {
+                if self.play_replay() {
+                    self.last_update = now;
+                    return Ok(());
+                }
+                if let Some(command) = self.ai_command() {
+                    self.apply(command);
+                }
+                let team = self.characters[self.char_id].team;
+                self.clock.tick(now - self.last_update, team);
+                if self.clock.flag_fallen(team) {
+                    self.lose_on_time(team);
+                } else if self.clock.turn_over() {
+                    // as if the player pressed Space
+                    let command = match self.action {
+                        Action::Attack => Command::Confirm,
+                        Action::Move => Command::EndTurn,
+                    };
+                    self.apply(command);
+                }
+            }
             // If we updated, we set our last_update to be now
-            self.last_update = Instant::now();
+            self.last_update = now;
         }
         // Finally we return `Ok` to indicate we didn't run into any errors
         Ok(())
     }
 
-    /// draw is where we should actually render the game's current state.
+    /// draw is where we should actually render the game's current state. The
+    /// scene stack clears the screen before and presents it after.
     fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
-        // First we clear the screen
-        graphics::clear(ctx);
-        // Draws the tiles
-        self.map.draw(ctx)?;
-        // Then we tell the characters to draw themselves
-        for character in self.characters.iter() {
-            character.draw(ctx)?;
-        }
-        match self.action {
-            Action::Attack => self.characters[self.char_id].draw_selector(ctx)?,
-            _ => ()
+        if self.editing {
+            if let Some(ref editor) = self.editor {
+                editor.draw(ctx, &self.camera, self.tileset.as_mut(), &self.font)?;
+            }
+        } else {
+            self.draw_game(ctx)?;
         }
-        // Finally we call graphics::present to cycle the gpu's framebuffer and display
-        // the new frame we just drew.
-        graphics::present(ctx);
-        // We yield the current thread until the next update
-        ggez::timer::yield_now();
-        // And return success.
         Ok(())
     }
 
@@ -366,79 +1345,252 @@ impl event::EventHandler for GameState {
         _keymod: event::Mod,
         _repeat: bool,
     ) {
-        let character = &mut self.characters[self.char_id];
-        println!("Character pos: {:?}", character.pos);
-        if character.mp == 0 {
-            self.action = Action::Attack;
-            character.selector = character.pos;
-            character.mp = character.mobi
+        if keycode == Keycode::F3 {
+            self.toggle_editor();
+            return;
         }
-        match self.action {
-            Action::Move => {
-                if let Some(dir) = Direction::from_keycode(keycode) {
-                    if self.map.is_available(character.pos, &dir) {
-                        self.map.tiles[character.pos.as_index()].char_id = NB_CHAR + 1;
-                        match dir {
-                            Direction::Up => character.pos.y -= 1,
-                            Direction::Down => character.pos.y += 1,
-                            Direction::Right => character.pos.x += 1,
-                            Direction::Left => character.pos.x -= 1,
-                        }
-                        self.map.tiles[character.pos.as_index()].char_id = self.char_id;
-                        character.mp -= 1;
-                    }
-                    character.facing = dir;
-                } else {
-                    match keycode {
-                        Keycode::Space => {
-                            self.action = Action::Attack;
-                            character.selector = character.pos;
-                            character.mp = character.mobi
-                        },
-                        _ => (),
-                    }
-                }
-            },
-            Action::Attack => {
-                if let Some(dir) = Direction::from_keycode(keycode) {
-                    match dir {
-                        Direction::Up => {
-                            character.selector.y -= 1;
-                        },
-                        Direction::Down => character.selector.y += 1,
-                        Direction::Right => character.selector.x += 1,
-                        Direction::Left => character.selector.x -= 1,
-                    }
-                } else {
-                    match keycode {
-                        Keycode::Space => {
-                            // end turn and change the state of the game
-                            self.turn += 1;
-                            self.action = Action::Move;
-                            self.char_id = (self.char_id + 1) % NB_CHAR;
-                        },
-                        _ => (),
+        if keycode == Keycode::F1 {
+            self.overlay = !self.overlay;
+            return;
+        }
+        if keycode == Keycode::F4 && !self.editing {
+            self.open_missions();
+            return;
+        }
+        if self.editing {
+            if let Some(ref mut editor) = self.editor {
+                editor.key_down_event(keycode, &mut self.camera);
+            }
+        } else if let Some(command) = Command::from_keycode(keycode) {
+            if self.takes_input() {
+                self.apply(command);
+            }
+            return;
+        } else if keycode == Keycode::F2 {
+            self.controls.toggle_mode();
+        } else if keycode == Keycode::F5 {
+            // a save replays with the roster, not with the enemies and the triggers of a scenario
+            if self.campaign.as_ref().map_or(false, |campaign| campaign.is_playing()) {
+                warn!("Campaign scenarios cannot be saved");
+            } else if self.gameover {
+                // the roster already took the match in, it would not play back the same
+                warn!("A finished game cannot be saved");
+            } else if let Err(e) = self.save_game(self.resources.join(replay::SAVE)) {
+                error!("Could not save the game: {}", e);
+            }
+        }
+        match keycode {
+            // the arrows are taken, the view moves with WASD
+            Keycode::W => self.camera.pan(0.0, -1.0),
+            Keycode::S => self.camera.pan(0.0, 1.0),
+            Keycode::A => self.camera.pan(-1.0, 0.0),
+            Keycode::D => self.camera.pan(1.0, 0.0),
+            Keycode::Equals | Keycode::Plus | Keycode::KpPlus => self.camera.zoom(1),
+            Keycode::Minus | Keycode::KpMinus => self.camera.zoom(-1),
+            _ => (),
+        }
+    }
+
+    /// resize_event gets fired when the window changes size. We keep one
+    /// drawing unit per window point so the board can be laid out again.
+    fn resize_event(&mut self, ctx: &mut Context, width: u32, height: u32) {
+        let screen = graphics::Rect::new(0.0, 0.0, width as f32, height as f32);
+        if let Err(e) = graphics::set_screen_coordinates(ctx, screen) {
+            error!("Could not resize the screen: {}", e);
+        }
+        self.camera.resize(width as f32, height as f32);
+    }
+
+    /// mouse_motion_event gets fired when the mouse moves, we remember the
+    /// hovered tile for the tooltip. Dragging with the middle button moves the view.
+    fn mouse_motion_event(
+        &mut self,
+        _ctx: &mut Context,
+        state: MouseState,
+        x: i32,
+        y: i32,
+        xrel: i32,
+        yrel: i32,
+    ) {
+        if state.middle() {
+            self.camera.drag(-xrel, -yrel);
+        }
+        self.camera.set_mouse(x, y);
+        self.hovered = self.camera.grid_at(x, y);
+        if self.editing {
+            if let Some(ref mut editor) = self.editor {
+                editor.mouse_motion_event(state.left(), state.right(), self.hovered);
+            }
+        }
+    }
+
+    /// mouse_wheel_event gets fired when the wheel turns, it zooms.
+    fn mouse_wheel_event(&mut self, _ctx: &mut Context, _x: i32, y: i32) {
+        self.camera.zoom(y.signum());
+    }
+
+    /// mouse_button_down_event gets fired on clicks. A left click picks the
+    /// attack target, clicking it a second time attacks; a right click cancels.
+    fn mouse_button_down_event(
+        &mut self,
+        _ctx: &mut Context,
+        button: MouseButton,
+        x: i32,
+        y: i32,
+    ) {
+        if self.editing {
+            if let Some(ref mut editor) = self.editor {
+                editor.mouse_button_down_event(button, self.camera.grid_at(x, y));
+            }
+            return;
+        }
+        if !self.takes_input() {
+            return;
+        }
+        match button {
+            MouseButton::Left => {
+                if let Some(pos) = self.camera.grid_at(x, y) {
+                    let selected = match self.action {
+                        Action::Attack => self.characters[self.char_id].selector == pos,
+                        Action::Move => false,
+                    };
+                    if selected {
+                        self.apply(Command::Confirm);
+                    } else {
+                        self.apply(Command::Select(pos));
                     }
                 }
             },
+            MouseButton::Right => self.apply(Command::Cancel),
+            _ => (),
+        }
+    }
+
+    /// controller_button_down_event gets fired when a gamepad button gets pressed.
+    fn controller_button_down_event(
+        &mut self,
+        _ctx: &mut Context,
+        btn: Button,
+        instance_id: i32,
+    ) {
+        if self.editing {
+            return;
+        }
+        if let Some(command) = Command::from_button(btn) {
+            let team = self.characters[self.char_id].team;
+            if self.takes_input() && self.controls.allows(instance_id, team) {
+                self.apply(command);
+            }
+        }
+    }
+
+    /// controller_axis_event gets fired when a gamepad stick moves, we treat
+    /// the left stick as a second d-pad.
+    fn controller_axis_event(
+        &mut self,
+        _ctx: &mut Context,
+        axis: Axis,
+        value: i16,
+        instance_id: i32,
+    ) {
+        if self.editing {
+            return;
+        }
+        if let Some(command) = self.controls.stick(axis, value, instance_id) {
+            let team = self.characters[self.char_id].team;
+            if self.takes_input() && self.controls.allows(instance_id, team) {
+                self.apply(command);
+            }
         }
     }
 }
 
 
+/// The `resources` directory of the crate when run through cargo, next to the
+/// working directory otherwise.
+fn resources_dir() -> PathBuf {
+    match env::var("CARGO_MANIFEST_DIR") {
+        Ok(manifest_dir) => Path::new(&manifest_dir).join("resources"),
+        Err(_) => PathBuf::from("resources"),
+    }
+}
+
 fn main() {
+    let resources = resources_dir();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match cli::Options::parse(&args) {
+        Ok(options) => options,
+        // nothing to log to yet
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        },
+    };
+    if options.help {
+        print!("{}", cli::USAGE);
+        return;
+    }
+    logger::init(&options.log).expect("Could not open the log file");
+    let map = match (&options.map, &options.generate) {
+        (&Some(ref path), _) => Map::load(path).expect("Could not load the map"),
+        (_, &Some(ref settings)) => {
+            info!("Generating a {:?} map with seed {}", settings.style, settings.seed);
+            settings.generate().expect("Could not generate a map")
+        },
+        _ => Map::parse(mapfile::DEFAULT_MAP).expect("The default map is broken"),
+    };
+    // batch matches between AIs don't need a window
+    if let Some(ref settings) = options.headless {
+        let stats = simulate::run(settings, map, &resources).expect("Could not run the simulation");
+        stats.write(settings).expect("Could not write the statistics");
+        return;
+    }
+    let mut window = ggez::conf::WindowMode::default().dimensions(options.window.0, options.window.1);
+    if options.fullscreen {
+        window = window.fullscreen_type(ggez::conf::FullscreenType::Desktop);
+    }
     let ctx = &mut ggez::ContextBuilder::new("ascii_war", "sheep")
-        .window_setup(ggez::conf::WindowSetup::default().title("Fight!"))
-        .window_mode(ggez::conf::WindowMode::default().dimensions(GRID_X as u32 * RES_X as u32, GRID_Y as u32 * RES_Y as u32))
+        .window_setup(ggez::conf::WindowSetup::default().title("Fight!").resizable(true).allow_highdpi(true))
+        .window_mode(window)
+        .add_resource_path(resources.clone())
         .build().expect("Failed to build ggez context");
     graphics::set_background_color(ctx, [0.0, 0.0, 0.0, 0.0].into());
 
-    let state = &mut GameState::new();
+    let mut state = GameState::new(map).expect("Failed to set up the game");
+    state.turn_mode = options.turn_mode;
+    state.clock = options.clock.clone();
+    state.load_assets(ctx, &resources);
+    // the assets have the roster and the items the game starts with
+    state.restart(state.start.clone()).expect("Failed to set up the game");
+    // no resize event comes for the size the window opens with
+    let (width, height) = graphics::get_drawable_size(ctx);
+    event::EventHandler::resize_event(&mut state, ctx, width, height);
+    state.seed = options.seed;
+    let mut scenes = SceneStack::new(state);
+    if let Some(ref name) = options.scenario {
+        scenes.game.play_scenario(name).expect("Could not start the scenario");
+        scenes.play();
+    } else if let Some(ref path) = options.load {
+        scenes.game.load_game(path, false).expect("Could not load the game");
+        scenes.play();
+    } else if let Some(ref path) = options.replay {
+        scenes.game.load_game(path, true).expect("Could not load the replay");
+        scenes.play();
+    } else if options.campaign {
+        scenes.game.open_missions();
+        scenes.play();
+    }
+    // once a save gave back its seed, and a different one per team, so two
+    // random AIs don't play the same
+    let seed = scenes.game.seed;
+    scenes.game.ai = options.ai.iter().enumerate()
+        .map(|(team, name)| ai::controller(name, seed.wrapping_add(team as u64)))
+        .collect();
     // And finally we actually run our game, passing in our context and state.
-    match event::run(ctx, state) {
+    match event::run(ctx, &mut scenes) {
         // If we encounter an error, we print it before exiting
-        Err(e) => println!("Error encountered running game: {}", e),
+        Err(e) => error!("Error encountered running game: {}", e),
         // And if not, we print a message saying we ran cleanly. Hooray!
-        Ok(_) => println!("Game exited cleanly!"),
+        Ok(_) => info!("Game exited cleanly!"),
     }
 }
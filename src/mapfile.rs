@@ -0,0 +1,145 @@
+use std::fs;
+use std::path::Path;
+
+use ggez::{GameError, GameResult};
+
+use objective::Objective;
+
+use super::{data, GridPosition, Map, Tile, TileState, NB_TEAM, WALL_HP};
+
+/// The map we play when none is given
+pub const DEFAULT_MAP: &'static str = include_str!("../resources/maps/arena.txt");
+
+/// Map files are data files with these sections:
+///
+/// - `[map]`: one `row = ...` per line of tiles, see `TileState::symbol`
+/// - `[cooldowns]`: `x y = cooldown` for the traps that have one
+/// - `[walls]`: `x y = hp` for the walls that do not have `WALL_HP`, 0 cannot be broken
+/// - `[spawns]`: `team = x y`, one per spawn point
+/// - `[items]`: `x y = item`, one per item lying on the ground
+/// - `[objective]`: how to win besides elimination, see `Objective::from_section`
+impl Map {
+    pub fn parse(text: &str) -> GameResult<Map> {
+        Map::from_sections(&data::parse(text)?)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> GameResult<Map> {
+        Map::from_sections(&data::load(path)?)
+    }
+
+    fn from_sections(sections: &[data::Section]) -> GameResult<Map> {
+        let error = |message: String| GameError::ResourceLoadError(format!("map: {}", message));
+        let rows: Vec<&str> = sections.iter()
+            .filter(|section| section.name == "map")
+            .flat_map(|section| section.entries.iter())
+            .filter(|entry| entry.0 == "row")
+            .map(|entry| entry.1.as_str())
+            .collect();
+        let width = rows.first().map_or(0, |row| row.chars().count());
+        if width == 0 || width > u16::max_value() as usize || rows.len() > u16::max_value() as usize {
+            return Err(error(String::from("no rows or too many")));
+        }
+        let mut map = Map::new(width as u16, rows.len() as u16, Tile::new(TileState::Empty));
+        for (y, row) in rows.iter().enumerate() {
+            if row.chars().count() != width {
+                return Err(error(format!("row {} is not {} tiles wide", y, width)));
+            }
+            for (x, symbol) in row.chars().enumerate() {
+                let state = TileState::from_symbol(symbol)
+                    .ok_or_else(|| error(format!("unknown tile `{}`", symbol)))?;
+                *map.tile_mut(GridPosition { x: x as u16, y: y as u16 }) = Tile::new(state);
+            }
+        }
+        for section in sections.iter() {
+            for &(ref key, ref value) in section.entries.iter() {
+                match section.name.as_str() {
+                    "cooldowns" => {
+                        let pos = map.position(data::pair(key)?).ok_or_else(|| error(format!("no tile at {}", key)))?;
+                        map.tile_mut(pos).cooldown = value.parse().map_err(|_| error(format!("bad cooldown {}", value)))?;
+                    },
+                    "walls" => {
+                        let pos = map.position(data::pair(key)?).ok_or_else(|| error(format!("no tile at {}", key)))?;
+                        if map.tile(pos).state != TileState::Wall {
+                            return Err(error(format!("no wall at {}", key)));
+                        }
+                        map.tile_mut(pos).hp = value.parse().map_err(|_| error(format!("bad hit points {}", value)))?;
+                    },
+                    "items" => {
+                        let pos = map.position(data::pair(key)?).ok_or_else(|| error(format!("no tile at {}", key)))?;
+                        map.items.push((pos, value.clone()));
+                    },
+                    "spawns" => {
+                        let team: usize = key.parse().map_err(|_| error(format!("bad team {}", key)))?;
+                        let pos = map.position(data::pair(value)?).ok_or_else(|| error(format!("no tile at {}", value)))?;
+                        if team >= NB_TEAM {
+                            return Err(error(format!("there is no team {}", team)));
+                        }
+                        map.spawns.push((team, pos));
+                    },
+                    _ => (),
+                }
+            }
+            if section.name == "objective" {
+                map.objective = Objective::from_section(section, &map)?;
+            }
+        }
+        Ok(map)
+    }
+
+    /// Checks that a pair of coordinates is on the map
+    fn position(&self, (x, y): (u16, u16)) -> Option<GridPosition> {
+        if x < self.width && y < self.height {
+            Some(GridPosition { x, y })
+        } else {
+            None
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::from("[map]\n");
+        for y in 0..self.height {
+            text.push_str("row = ");
+            for x in 0..self.width {
+                text.push(self.tile(GridPosition { x, y }).state.symbol());
+            }
+            text.push('\n');
+        }
+        text.push_str("\n[cooldowns]\n");
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let tile = self.tile(GridPosition { x, y });
+                if tile.cooldown > 0 {
+                    text.push_str(&format!("{} {} = {}\n", x, y, tile.cooldown));
+                }
+            }
+        }
+        text.push_str("\n[walls]\n");
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let tile = self.tile(GridPosition { x, y });
+                if tile.state == TileState::Wall && tile.hp != WALL_HP {
+                    text.push_str(&format!("{} {} = {}\n", x, y, tile.hp));
+                }
+            }
+        }
+        text.push_str("\n[spawns]\n");
+        for &(team, pos) in self.spawns.iter() {
+            text.push_str(&format!("{} = {} {}\n", team, pos.x, pos.y));
+        }
+        text.push_str("\n[items]\n");
+        for &(pos, ref key) in self.items.iter() {
+            text.push_str(&format!("{} {} = {}\n", pos.x, pos.y, key));
+        }
+        text.push('\n');
+        text.push_str(&self.objective.to_text());
+        text
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> GameResult<()> {
+        if let Some(dir) = path.as_ref().parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_text())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,302 @@
+use ggez::{GameError, GameResult};
+
+use rng::Rng;
+
+use super::{Direction, GridPosition, Map, Tile, TileState, GRID_X, GRID_Y, NB_TEAM};
+
+/// Smallest map we generate, anything less is all border
+pub const MIN_SIZE: u16 = 5;
+/// Spawn points of each team, those who don't get one stand on the first
+/// free tile
+const SPAWNS_PER_TEAM: usize = 4;
+/// How many maps we try before giving up on getting a connected one
+const ATTEMPTS: u32 = 100;
+/// Part of the inside of a cave that starts as wall
+const CAVE_FILL: f32 = 0.45;
+const CAVE_STEPS: u32 = 4;
+/// Rooms are from 3 to 6 tiles wide and high
+const ROOM_MIN: u32 = 3;
+const ROOM_MAX: u32 = 6;
+const ROOM_TRIES: u32 = 30;
+
+/// The kinds of maps we know how to build
+#[derive(Debug, Copy, Clone)]
+pub enum Style {
+    /// Cellular automata caves
+    Caves,
+    /// Rectangular rooms joined by corridors
+    Rooms,
+    /// An open field with a few pillars
+    Arena,
+}
+
+impl Style {
+    pub fn from_name(name: &str) -> Option<Style> {
+        match name {
+            "caves" => Some(Style::Caves),
+            "rooms" => Some(Style::Rooms),
+            "arena" => Some(Style::Arena),
+            _ => None,
+        }
+    }
+}
+
+/// What to generate. The same settings always give the same map.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub style: Style,
+    pub width: u16,
+    pub height: u16,
+    pub seed: u64,
+    /// Part of the floor that gets a trap, from 0 to 1
+    pub traps: f32,
+    /// Whether the second half of the map is the first half turned around,
+    /// so that no team gets a better side
+    pub symmetric: bool,
+}
+
+impl Settings {
+    pub fn new(style: Style, seed: u64) -> Self {
+        Settings {
+            style: style,
+            width: GRID_X,
+            height: GRID_Y,
+            seed: seed,
+            traps: 0.03,
+            symmetric: true,
+        }
+    }
+
+    /// Builds maps until one has every spawn point connected to the others.
+    pub fn generate(&self) -> GameResult<Map> {
+        let mut rng = Rng::new(self.seed);
+        for _ in 0..ATTEMPTS {
+            let mut map = Map::new(self.width, self.height, Tile::new(TileState::Wall));
+            match self.style {
+                Style::Caves => caves(&mut map, &mut rng),
+                Style::Rooms => rooms(&mut map, &mut rng),
+                Style::Arena => arena(&mut map, &mut rng),
+            }
+            if self.symmetric {
+                mirror(&mut map);
+            }
+            map.spawns = match self.spawns(&map) {
+                Some(spawns) => spawns,
+                None => continue,
+            };
+            if !map.is_connected() {
+                continue;
+            }
+            // pockets nobody can walk to are just walls
+            let reachable = map.reachable(map.spawns[0].1);
+            for (tile, &reached) in map.tiles.iter_mut().zip(reachable.iter()) {
+                if !reached {
+                    tile.state = TileState::Wall;
+                }
+            }
+            self.sprinkle_traps(&mut map, &mut rng);
+            if map.is_connected() {
+                return Ok(map);
+            }
+        }
+        Err(GameError::ResourceLoadError(format!("no connected {:?} map after {} tries", self.style, ATTEMPTS)))
+    }
+
+    /// The floor tiles closest to the corner of each team, as many as fit up
+    /// to `SPAWNS_PER_TEAM`. With two teams on a symmetric map the second
+    /// team gets the mirror of the first one's.
+    fn spawns(&self, map: &Map) -> Option<Vec<(usize, GridPosition)>> {
+        let (right, bottom) = (map.width - 2, map.height - 2);
+        let corners = [(1, 1), (right, bottom), (right, 1), (1, bottom)];
+        let mut spawns: Vec<(usize, GridPosition)> = Vec::new();
+        for team in 0..NB_TEAM {
+            if self.symmetric && NB_TEAM == 2 && team == 1 {
+                let mirrored: Vec<_> = spawns.iter().map(|&(_, pos)| (1, mirror_position(map, pos))).collect();
+                // a spawn in the very middle is its own mirror
+                if mirrored.iter().any(|&(_, pos)| spawns.iter().any(|&(_, spawn)| spawn == pos)) {
+                    return None;
+                }
+                spawns.extend(mirrored);
+                continue;
+            }
+            let corner = GridPosition::from(corners[team % corners.len()]);
+            let mut floor: Vec<GridPosition> = (0..map.tiles.len())
+                .map(|index| position_of(map, index))
+                .filter(|&pos| map.tile(pos).is_empty() && !spawns.iter().any(|&(_, spawn)| spawn == pos))
+                .collect();
+            floor.sort_by_key(|pos| pos.distance(corner));
+            // leave room for the other teams on small maps
+            let per_team = SPAWNS_PER_TEAM.min(floor.len() / (2 * NB_TEAM)).max(1);
+            if floor.len() < per_team {
+                return None;
+            }
+            spawns.extend(floor[..per_team].iter().map(|&pos| (team, pos)));
+        }
+        Some(spawns)
+    }
+
+    /// Turns some of the floor into traps, never under a spawn point.
+    fn sprinkle_traps(&self, map: &mut Map, rng: &mut Rng) {
+        let count = map.tiles.len();
+        let half = if self.symmetric { (count + 1) / 2 } else { count };
+        for index in 0..half {
+            let pos = position_of(map, index);
+            let spawn = map.spawns.iter().any(|&(_, spawn)| spawn == pos);
+            if spawn || !map.tile(pos).is_empty() || !rng.chance(self.traps) {
+                continue;
+            }
+            map.tiles[index].state = TileState::Trap;
+            if self.symmetric {
+                map.tiles[count - 1 - index].state = TileState::Trap;
+            }
+        }
+    }
+}
+
+/// Checks whether `(x, y)` is inside the border of the map
+fn inside(map: &Map, x: u32, y: u32) -> bool {
+    x > 0 && y > 0 && x < map.width as u32 - 1 && y < map.height as u32 - 1
+}
+
+fn position_of(map: &Map, index: usize) -> GridPosition {
+    GridPosition { x: (index % map.width as usize) as u16, y: (index / map.width as usize) as u16 }
+}
+
+/// The tile across the center of the map
+fn mirror_position(map: &Map, pos: GridPosition) -> GridPosition {
+    GridPosition { x: map.width - 1 - pos.x, y: map.height - 1 - pos.y }
+}
+
+/// Copies the first half of the map over the second one, turned around the center.
+fn mirror(map: &mut Map) {
+    let count = map.tiles.len();
+    for index in 0..count / 2 {
+        map.tiles[count - 1 - index] = map.tiles[index];
+    }
+}
+
+fn set_floor(map: &mut Map, x: u32, y: u32) {
+    if inside(map, x, y) {
+        map.tile_mut(GridPosition { x: x as u16, y: y as u16 }).state = TileState::Empty;
+    }
+}
+
+/// Random noise smoothed out: a tile becomes a wall when most of its
+/// neighbours are, the outside of the map counting as wall.
+fn caves(map: &mut Map, rng: &mut Rng) {
+    let (width, height) = (map.width as u32, map.height as u32);
+    for y in 0..height {
+        for x in 0..width {
+            if !rng.chance(CAVE_FILL) {
+                set_floor(map, x, y);
+            }
+        }
+    }
+    for _ in 0..CAVE_STEPS {
+        let previous = map.clone();
+        for y in 1..height - 1 {
+            for x in 1..width - 1 {
+                let mut walls = 0;
+                for ny in y - 1..y + 2 {
+                    for nx in x - 1..x + 2 {
+                        if !previous.tile(GridPosition { x: nx as u16, y: ny as u16 }).is_empty() {
+                            walls += 1;
+                        }
+                    }
+                }
+                let state = if walls >= 5 { TileState::Wall } else { TileState::Empty };
+                map.tile_mut(GridPosition { x: x as u16, y: y as u16 }).state = state;
+            }
+        }
+    }
+}
+
+/// Rooms that do not overlap, each joined to the previous one by an L shaped corridor.
+fn rooms(map: &mut Map, rng: &mut Rng) {
+    let (width, height) = (map.width as u32, map.height as u32);
+    let mut placed: Vec<(u32, u32, u32, u32)> = Vec::new();
+    for _ in 0..ROOM_TRIES {
+        let w = rng.range(ROOM_MIN, ROOM_MAX).min(width - 2);
+        let h = rng.range(ROOM_MIN, ROOM_MAX).min(height - 2);
+        let x = rng.range(1, width - 1 - w);
+        let y = rng.range(1, height - 1 - h);
+        // keep a wall between two rooms
+        let overlaps = placed.iter().any(|&(px, py, pw, ph)| {
+            x <= px + pw && px <= x + w && y <= py + ph && py <= y + h
+        });
+        if overlaps {
+            continue;
+        }
+        for ry in y..y + h {
+            for rx in x..x + w {
+                set_floor(map, rx, ry);
+            }
+        }
+        if let Some(&(px, py, pw, ph)) = placed.last() {
+            let (x0, y0) = (px + pw / 2, py + ph / 2);
+            let (x1, y1) = (x + w / 2, y + h / 2);
+            for cx in x0.min(x1)..x0.max(x1) + 1 {
+                set_floor(map, cx, y0);
+            }
+            for cy in y0.min(y1)..y0.max(y1) + 1 {
+                set_floor(map, x1, cy);
+            }
+        }
+        placed.push((x, y, w, h));
+    }
+}
+
+/// Open ground with a sprinkle of one or two tile pillars to hide behind.
+fn arena(map: &mut Map, rng: &mut Rng) {
+    let (width, height) = (map.width as u32, map.height as u32);
+    for y in 0..height {
+        for x in 0..width {
+            set_floor(map, x, y);
+        }
+    }
+    for _ in 0..width * height / 16 {
+        let x = rng.range(2, width - 3);
+        let y = rng.range(2, height - 3);
+        map.tile_mut(GridPosition { x: x as u16, y: y as u16 }).state = TileState::Wall;
+        if rng.chance(0.5) {
+            let (x, y) = if rng.chance(0.5) { (x + 1, y) } else { (x, y + 1) };
+            if inside(map, x, y) {
+                map.tile_mut(GridPosition { x: x as u16, y: y as u16 }).state = TileState::Wall;
+            }
+        }
+    }
+}
+
+impl Map {
+    /// The tiles that can be walked to from `start`, by index
+    pub fn reachable(&self, start: GridPosition) -> Vec<bool> {
+        let mut reached = vec![false; self.tiles.len()];
+        reached[start.as_index(self.width)] = true;
+        let mut todo = vec![start];
+        while let Some(pos) = todo.pop() {
+            for dir in [Direction::Up, Direction::Down, Direction::Left, Direction::Right].iter() {
+                // everybody has to get there, swimmers or not
+                if !self.is_available(pos, dir, false) {
+                    continue;
+                }
+                let next = self.step(pos, dir).unwrap();
+                if !reached[next.as_index(self.width)] {
+                    reached[next.as_index(self.width)] = true;
+                    todo.push(next);
+                }
+            }
+        }
+        reached
+    }
+
+    /// Whether every spawn point can walk to every other one
+    pub fn is_connected(&self) -> bool {
+        match self.spawns.first() {
+            Some(&(_, start)) => {
+                let reached = self.reachable(start);
+                self.spawns.iter().all(|&(_, pos)| reached[pos.as_index(self.width)])
+            },
+            None => true,
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use ggez::{GameError, GameResult};
+
+use super::{data, GameState, GridPosition, Map, NB_TEAM, NO_CHAR};
+
+/// How a game is won, besides being the last team standing which always counts.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Objective {
+    /// Nothing more than elimination
+    Eliminate,
+    /// The first team to keep a character on the tile for this many turns in
+    /// a row, counting the turn of every character
+    Capture { pos: GridPosition, character_turns: u32 },
+    /// The first character of the team, or of anyone without a team, to step on the tile
+    Exit { pos: GridPosition, team: Option<usize> },
+    /// The team wins if it still stands after this many turns, counting the
+    /// turn of every character
+    Survive { team: usize, character_turns: u32 },
+    /// Killing the leader makes the other team win
+    Assassinate { leader: usize },
+}
+
+impl Objective {
+    /// Reads an `[objective]` section: a `type` out of `eliminate`, `capture`,
+    /// `exit`, `survive` and `assassinate`, with its `tile = x y`, `character_turns`,
+    /// `team` or `leader` as needed.
+    pub fn from_section(section: &data::Section, map: &Map) -> GameResult<Objective> {
+        let error = |message: String| GameError::ResourceLoadError(format!("objective: {}", message));
+        let tile = || -> GameResult<GridPosition> {
+            let (x, y) = data::pair(section.get("tile").ok_or_else(|| error(String::from("missing tile")))?)?;
+            if x < map.width && y < map.height {
+                Ok(GridPosition { x, y })
+            } else {
+                Err(error(format!("no tile at {} {}", x, y)))
+            }
+        };
+        let team = |team: usize| if team < NB_TEAM { Ok(team) } else { Err(error(format!("there is no team {}", team))) };
+        let objective = match section.parse::<String>("type")?.as_str() {
+            "eliminate" => Objective::Eliminate,
+            "capture" => Objective::Capture { pos: tile()?, character_turns: section.parse("character_turns")? },
+            "exit" => {
+                let exit_team = match section.get("team") {
+                    Some(_) => Some(team(section.parse("team")?)?),
+                    None => None,
+                };
+                Objective::Exit { pos: tile()?, team: exit_team }
+            },
+            "survive" => Objective::Survive { team: team(section.parse("team")?)?, character_turns: section.parse("character_turns")? },
+            "assassinate" => {
+                // checked against the characters once they are known
+                Objective::Assassinate { leader: section.parse("leader")? }
+            },
+            other => return Err(error(format!("unknown type {}", other))),
+        };
+        Ok(objective)
+    }
+
+    /// The `[objective]` section giving back this objective
+    pub fn to_text(&self) -> String {
+        match *self {
+            Objective::Eliminate => String::from("[objective]\ntype = eliminate\n"),
+            Objective::Capture { pos, character_turns } => {
+                format!("[objective]\ntype = capture\ntile = {} {}\ncharacter_turns = {}\n", pos.x, pos.y, character_turns)
+            },
+            Objective::Exit { pos, team } => {
+                let team = team.map_or(String::new(), |team| format!("team = {}\n", team));
+                format!("[objective]\ntype = exit\ntile = {} {}\n{}", pos.x, pos.y, team)
+            },
+            Objective::Survive { team, character_turns } => {
+                format!("[objective]\ntype = survive\nteam = {}\ncharacter_turns = {}\n", team, character_turns)
+            },
+            Objective::Assassinate { leader } => format!("[objective]\ntype = assassinate\nleader = {}\n", leader),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match *self {
+            Objective::Eliminate => String::from("Defeat the other team"),
+            Objective::Capture { pos, character_turns } => format!("Hold ({}, {}) for {} character turns", pos.x, pos.y, character_turns),
+            Objective::Exit { pos, team: Some(team) } => format!("Team {}: reach ({}, {})", team, pos.x, pos.y),
+            Objective::Exit { pos, team: None } => format!("Reach ({}, {})", pos.x, pos.y),
+            Objective::Survive { team, character_turns } => format!("Team {}: survive {} character turns", team, character_turns),
+            Objective::Assassinate { leader } => format!("Defeat or protect character {}", leader),
+        }
+    }
+
+    /// The tile the objective is about, to show it on the board
+    pub fn tile(&self) -> Option<GridPosition> {
+        match *self {
+            Objective::Capture { pos, .. } | Objective::Exit { pos, .. } => Some(pos),
+            _ => None,
+        }
+    }
+}
+
+/// How a game ended
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    /// Nobody wins when everybody died
+    pub winner: Option<usize>,
+    pub reason: String,
+}
+
+/// What a character did during a game
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Record {
+    pub damage_dealt: u32,
+    pub damage_taken: u32,
+    pub kills: u32,
+}
+
+impl GameState {
+    /// The teams that still have someone standing
+    fn teams_alive(&self) -> Vec<usize> {
+        let mut teams: Vec<usize> = self.characters.iter()
+            .filter(|character| character.is_alive())
+            .map(|character| character.team)
+            .collect();
+        teams.sort();
+        teams.dedup();
+        teams
+    }
+
+    /// Looks for a winner, after every action. The game is over once there is one.
+    pub fn check_victory(&mut self) {
+        if self.outcome.is_some() {
+            return;
+        }
+        let alive = self.teams_alive();
+        let outcome = if alive.len() <= 1 {
+            Some(Outcome { winner: alive.first().cloned(), reason: String::from("last team standing") })
+        } else {
+            match self.map.objective {
+                Objective::Eliminate => None,
+                Objective::Capture { character_turns, .. } => match self.held {
+                    Some((team, held)) if held >= character_turns => {
+                        Some(Outcome { winner: Some(team), reason: format!("held the tile for {} character turns", held) })
+                    },
+                    _ => None,
+                },
+                Objective::Exit { pos, team } => {
+                    let id = self.map.tile(pos).char_id;
+                    if id != NO_CHAR && team.map_or(true, |team| team == self.characters[id].team) {
+                        Some(Outcome { winner: Some(self.characters[id].team), reason: format!("character {} reached the exit", id) })
+                    } else {
+                        None
+                    }
+                },
+                Objective::Survive { team, character_turns } => {
+                    if self.turn >= character_turns && alive.contains(&team) {
+                        Some(Outcome { winner: Some(team), reason: format!("survived {} character turns", character_turns) })
+                    } else {
+                        None
+                    }
+                },
+                Objective::Assassinate { leader } => {
+                    let leader = &self.characters[leader];
+                    if leader.is_alive() {
+                        None
+                    } else {
+                        let winner = alive.iter().cloned().find(|&team| team != leader.team);
+                        Some(Outcome { winner: winner, reason: format!("character {} was defeated", leader.id) })
+                    }
+                },
+            }
+        };
+        if let Some(outcome) = outcome {
+            self.end_game(outcome);
+        }
+    }
+
+    /// The game is over, whatever ended it
+    pub fn end_game(&mut self, outcome: Outcome) {
+        if self.outcome.is_some() {
+            return;
+        }
+        debug!("Game over after {} turns, winner {:?}: {}", self.turn, outcome.winner, outcome.reason);
+        self.gameover = true;
+        self.outcome = Some(outcome);
+        self.finish_match();
+        self.finish_scenario();
+    }
+
+    /// A team whose chess clock is empty loses, to the other team still standing
+    pub fn lose_on_time(&mut self, team: usize) {
+        let winner = (0..NB_TEAM).find(|&other| {
+            other != team && self.characters.iter().any(|character| character.team == other && character.is_alive())
+        });
+        self.end_game(Outcome { winner: winner, reason: format!("team {} ran out of time", team) });
+    }
+
+    /// At the end of each turn, counts how long the same team has been on the capture tile.
+    pub fn count_hold(&mut self) {
+        if let Objective::Capture { pos, .. } = self.map.objective {
+            let id = self.map.tile(pos).char_id;
+            self.held = if id != NO_CHAR {
+                let team = self.characters[id].team;
+                match self.held {
+                    Some((held_team, turns)) if held_team == team => Some((team, turns + 1)),
+                    _ => Some((team, 1)),
+                }
+            } else {
+                None
+            };
+        }
+    }
+
+    /// The objective and how far along it is, for the HUD
+    pub fn objective_lines(&self) -> Vec<String> {
+        let mut lines = vec![self.map.objective.describe()];
+        match self.map.objective {
+            Objective::Capture { character_turns, .. } => {
+                if let Some((team, held)) = self.held {
+                    lines.push(format!("Team {} holds it: {}/{}", team, held, character_turns));
+                }
+            },
+            Objective::Survive { character_turns, .. } => lines.push(format!("Character turn {}/{}", self.turn, character_turns)),
+            _ => (),
+        }
+        lines
+    }
+
+    /// The results screen: who won, why, and what everyone did
+    pub fn results_lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if let Some(ref outcome) = self.outcome {
+            lines.push(match outcome.winner {
+                Some(team) => format!("Team {} wins: {}", team, outcome.reason),
+                None => String::from("Nobody wins"),
+            });
+        }
+        lines.push(format!("{} character turns played", self.turn));
+        for (character, record) in self.characters.iter().zip(self.records.iter()) {
+            let state = if character.is_alive() { format!("{} HP left", character.hp) } else { String::from("defeated") };
+            lines.push(format!("{} (team {}, {}): {}, {} damage dealt, {} taken, {} kills",
+                               character.name, character.team, character.class.key(), state,
+                               record.damage_dealt, record.damage_taken, record.kills));
+        }
+        lines.extend(self.progress.iter().cloned());
+        let again = if self.campaign.as_ref().map_or(false, |campaign| campaign.is_playing()) { "missions" } else { "play again" };
+        lines.push(format!("Return / Start: {}, Escape / B: title", again));
+        lines
+    }
+}
@@ -0,0 +1,104 @@
+use ggez::{graphics, timer, Context, GameResult};
+
+use ai::Paths;
+use hud;
+
+use super::{Action, GameState, GridPosition, TileState, NO_CHAR};
+
+/// Colors of the tiles the current character can walk to, and of those it
+/// can attack
+const REACHED_COLOR: [f32; 4] = [0.2, 0.8, 0.3, 0.25];
+const IN_RANGE_COLOR: [f32; 4] = [0.9, 0.2, 0.2, 0.25];
+/// The line of sight to the aimed tile, clear or blocked
+const SIGHT_COLOR: [f32; 4] = [0.3, 1.0, 0.3, 0.9];
+const BLOCKED_COLOR: [f32; 4] = [1.0, 0.2, 0.2, 0.9];
+
+impl GameState {
+    /// Draws what developers need to see over the board: the index, the
+    /// occupant and the cooldown of each tile, where the current character
+    /// can walk or shoot, its line of sight and the state of the rules.
+    pub fn draw_overlay(&self, ctx: &mut Context) -> GameResult<()> {
+        let character = &self.characters[self.char_id];
+        let (x0, y0, x1, y1) = self.camera.visible();
+        let paths = Paths::new(&self.map, character.pos, character.class.swims(), character.mp as u32);
+        let high = if self.map.tile(character.pos).state == TileState::High { 1 } else { 0 };
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let pos = GridPosition { x: x, y: y };
+                let rect = self.camera.rect(pos);
+                let tile = self.map.tile(pos);
+                // walking in move mode, shooting in attack mode
+                let color = match self.action {
+                    Action::Move if paths.spent(pos).is_some() => Some(REACHED_COLOR),
+                    Action::Attack if pos != character.pos && pos.distance(character.pos) <= character.class.range() + high
+                        && self.map.line_of_sight(character.pos, pos) => Some(IN_RANGE_COLOR),
+                    _ => None,
+                };
+                if let Some(color) = color {
+                    graphics::set_color(ctx, color.into())?;
+                    graphics::rectangle(ctx, graphics::DrawMode::Fill, rect)?;
+                }
+                let mut lines = vec![format!("#{}", pos.as_index(self.map.width))];
+                if tile.char_id != NO_CHAR {
+                    lines.push(format!("c{}", tile.char_id));
+                }
+                if tile.cooldown > 0 {
+                    lines.push(format!("cd{}", tile.cooldown));
+                }
+                if let (Action::Move, Some(mp)) = (self.action, paths.spent(pos)) {
+                    lines.push(format!("{}mp", mp));
+                }
+                self.draw_small_text(ctx, &lines, rect)?;
+            }
+        }
+        // the line of sight to the selector, or to the hovered tile while moving
+        let aimed = match self.action {
+            Action::Attack => Some(character.selector),
+            Action::Move => self.hovered,
+        };
+        match aimed {
+            Some(target) if target != character.pos && self.map.contains(target) => {
+                let (from, to) = (self.camera.rect(character.pos), self.camera.rect(target));
+                let color = if self.map.line_of_sight(character.pos, target) { SIGHT_COLOR } else { BLOCKED_COLOR };
+                graphics::set_color(ctx, color.into())?;
+                graphics::line(ctx, &[
+                    graphics::Point2::new(from.x + from.w / 2.0, from.y + from.h / 2.0),
+                    graphics::Point2::new(to.x + to.w / 2.0, to.y + to.h / 2.0),
+                ], (from.w / 16.0).max(1.0))?;
+            },
+            _ => (),
+        }
+        self.draw_state(ctx)
+    }
+
+    /// The state of the rules, in the top right corner
+    fn draw_state(&self, ctx: &mut Context) -> GameResult<()> {
+        let character = &self.characters[self.char_id];
+        let lines = vec![
+            format!("FPS {:.0}", timer::get_fps(ctx)),
+            format!("Turn {}, character {} ({:?} turns)", self.turn, self.char_id, self.turn_mode),
+            format!("Action {:?}", self.action),
+            format!("Pos ({}, {}), selector ({}, {})", character.pos.x, character.pos.y, character.selector.x, character.selector.y),
+            format!("MP {}/{}, HP {}/{}", character.mp, character.mobi, character.hp, character.max_hp),
+            format!("Events {}, animations {}", self.events.len(), if self.animations.busy() { "busy" } else { "idle" }),
+        ];
+        hud::draw_top_right(ctx, &self.font, &lines, self.camera.window(), self.camera.tile_width() / 8.0)
+    }
+
+    /// A few lines of text in the top left corner of a tile, as small as
+    /// needed to fit
+    fn draw_small_text(&self, ctx: &mut Context, lines: &[String], rect: graphics::Rect) -> GameResult<()> {
+        graphics::set_color(ctx, [1.0, 1.0, 1.0, 0.9].into())?;
+        let line_height = rect.h / 4.0;
+        for (i, line) in lines.iter().enumerate() {
+            let text = graphics::Text::new(ctx, line, &self.font)?;
+            let scale = (line_height / text.height().max(1) as f32).min(1.0);
+            graphics::draw_ex(ctx, &text, graphics::DrawParam {
+                dest: graphics::Point2::new(rect.x + 1.0, rect.y + i as f32 * line_height),
+                scale: graphics::Point2::new(scale, scale),
+                ..Default::default()
+            })?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+use super::{Action, GameState, NB_TEAM};
+
+/// How turns are given out
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TurnMode {
+    /// One character at a time, in the order of the `Timeline`
+    Initiative,
+    /// Each team in turn plays all of its characters, in the order it likes
+    Phases,
+}
+
+impl GameState {
+    /// Who plays first in a new game
+    pub fn first_actor(&mut self) -> Option<usize> {
+        match self.turn_mode {
+            TurnMode::Initiative => self.timeline.current(&self.characters),
+            TurnMode::Phases => self.start_phase(0),
+        }
+    }
+
+    /// Who plays once the selected character is done: the next one of the
+    /// same team in phases mode, or the first of the next team when they all acted.
+    pub fn next_actor(&mut self) -> Option<usize> {
+        match self.turn_mode {
+            TurnMode::Initiative => self.timeline.current(&self.characters),
+            TurnMode::Phases => {
+                let team = self.characters[self.char_id].team;
+                match self.ready_unit(team, self.char_id) {
+                    Some(id) => Some(id),
+                    None => self.start_phase((team + 1) % NB_TEAM),
+                }
+            },
+        }
+    }
+
+    /// Gives the next team with someone standing, from `team` on, a phase
+    fn start_phase(&mut self, team: usize) -> Option<usize> {
+        for step in 0..NB_TEAM {
+            let team = (team + step) % NB_TEAM;
+            for character in self.characters.iter_mut().filter(|character| character.team == team) {
+                character.acted = false;
+            }
+            if let Some(id) = self.ready_unit(team, self.characters.len() - 1) {
+                return Some(id);
+            }
+        }
+        None
+    }
+
+    /// The first living character of `team` that did not act this phase,
+    /// looking from the one after `after`, and `after` itself last.
+    fn ready_unit(&self, team: usize, after: usize) -> Option<usize> {
+        let count = self.characters.len();
+        (1..count + 1)
+            .map(|step| (after + step) % count)
+            .find(|&id| {
+                let character = &self.characters[id];
+                character.team == team && character.is_alive() && !character.acted
+            })
+    }
+
+    /// Whether the selected character can still hand over to another one:
+    /// in phases mode, as long as it did not start moving.
+    fn can_switch(&self) -> bool {
+        let character = &self.characters[self.char_id];
+        match (self.turn_mode, &self.action) {
+            (TurnMode::Phases, &Action::Move) => character.mp == character.mobi,
+            _ => false,
+        }
+    }
+
+    /// Selects the next character of the team that has not acted yet
+    pub fn cycle_unit(&mut self) {
+        if !self.can_switch() {
+            return;
+        }
+        let team = self.characters[self.char_id].team;
+        if let Some(id) = self.ready_unit(team, self.char_id) {
+            self.select_unit(id);
+        }
+    }
+
+    /// Selects the character `id` if it is of the team in play and still has to act
+    pub fn select_unit(&mut self, id: usize) {
+        let (team, pos, ready) = {
+            let character = &self.characters[id];
+            (character.team, character.pos, character.is_alive() && !character.acted)
+        };
+        if self.can_switch() && ready && team == self.characters[self.char_id].team {
+            self.char_id = id;
+            self.camera.center_on(pos);
+        }
+    }
+
+    /// Ends the phase of the team in play: those who did not act yet won't.
+    pub fn end_phase(&mut self) {
+        if self.turn_mode != TurnMode::Phases {
+            return;
+        }
+        let team = self.characters[self.char_id].team;
+        for character in self.characters.iter_mut().filter(|character| character.team == team) {
+            character.acted = true;
+        }
+        self.end_turn();
+    }
+
+    /// The next characters to play, the selected one first, for the HUD
+    pub fn upcoming(&self, count: usize) -> Vec<usize> {
+        match self.turn_mode {
+            TurnMode::Initiative => self.timeline.forecast(&self.characters, count),
+            TurnMode::Phases => {
+                // the rest of this phase, then the next teams
+                let team = self.characters[self.char_id].team;
+                let mut ids = vec![self.char_id];
+                let mut after = self.char_id;
+                while let Some(id) = self.ready_unit(team, after) {
+                    if ids.contains(&id) {
+                        break;
+                    }
+                    ids.push(id);
+                    after = id;
+                }
+                for step in 1..NB_TEAM + 1 {
+                    let team = (team + step) % NB_TEAM;
+                    ids.extend(self.characters.iter()
+                        .filter(|character| character.team == team && character.is_alive())
+                        .map(|character| character.id));
+                }
+                ids.truncate(count);
+                ids
+            },
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use std::fs;
+use std::path::Path;
+
+use ggez::{GameError, GameResult};
+
+use input::Command;
+use item::Items;
+use phase::TurnMode;
+use roster::Roster;
+
+use super::{data, GameState, Map};
+
+/// Where F5 saves the game in progress, in the resources
+pub const SAVE: &'static str = "saves/game.txt";
+
+/// Sections of a save keeping the roster and the items it was played with
+const ROSTER: &'static str = "roster";
+const ITEM: &'static str = "item";
+
+/// A data file written after the map: its entries before the first header
+/// go under `[prefix]`, and its sections under `[prefix name]`.
+fn nest(text: &str, prefix: &str) -> String {
+    let mut nested = format!("\n[{}]\n", prefix);
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') && line.ends_with(']') {
+            nested.push_str(&format!("[{} {}]\n", prefix, &line[1..line.len() - 1]));
+        } else {
+            nested.push_str(line);
+            nested.push('\n');
+        }
+    }
+    nested
+}
+
+/// The sections `nest` wrote, as they were in their own file
+fn unnest(sections: &[data::Section], prefix: &str) -> Vec<data::Section> {
+    let nested = format!("{} ", prefix);
+    sections.iter()
+        .filter_map(|section| {
+            let name = if section.name == prefix {
+                String::new()
+            } else if section.name.starts_with(&nested) {
+                section.name[nested.len()..].to_string()
+            } else {
+                return None;
+            };
+            Some(data::Section { name: name, entries: section.entries.clone() })
+        })
+        .collect()
+}
+
+impl GameState {
+    /// The map the game started on, then a `[replay]` section with the turn
+    /// `mode`, the `seed` of the AIs and every `command` played so far, in
+    /// order, then the roster and the items the game was played with. It
+    /// loads back as a map too.
+    pub fn replay_text(&self) -> String {
+        let mut text = self.start.to_text();
+        text.push_str("\n[replay]\n");
+        text.push_str(&format!("mode = {}\n", match self.turn_mode {
+            TurnMode::Initiative => "initiative",
+            TurnMode::Phases => "phases",
+        }));
+        text.push_str(&format!("seed = {}\n", self.seed));
+        for command in self.history.iter() {
+            text.push_str(&format!("command = {}\n", command.to_text()));
+        }
+        text.push_str(&nest(&self.roster.to_text(), ROSTER));
+        text.push_str(&nest(&self.items.to_text(), ITEM));
+        text
+    }
+
+    pub fn save_game<P: AsRef<Path>>(&self, path: P) -> GameResult<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.replay_text())?;
+        info!("Game saved to {}", path.display());
+        Ok(())
+    }
+
+    /// Starts the game of a replay file over, and plays its commands: all at
+    /// once to go on from where it was saved, or one at a time to watch it.
+    /// The roster and the items must be those it was saved with, the
+    /// commands would play another game otherwise.
+    pub fn load_game<P: AsRef<Path>>(&mut self, path: P, watch: bool) -> GameResult<()> {
+        let name = path.as_ref().display().to_string();
+        let sections = data::load(&path)?;
+        let map = Map::load(&path)?;
+        let replay = sections.iter().find(|section| section.name == "replay")
+            .ok_or_else(|| GameError::ResourceLoadError(format!("{} is not a replay", name)))?;
+        let roster = unnest(&sections, ROSTER);
+        if roster.is_empty() {
+            return Err(GameError::ResourceLoadError(format!("{} does not say which roster it was played with", name)));
+        }
+        if Roster::from_sections(&roster)?.to_text() != self.roster.to_text() {
+            return Err(GameError::ResourceLoadError(format!("{} was played with another roster", name)));
+        }
+        if Items::from_sections(&unnest(&sections, ITEM))?.to_text() != self.items.to_text() {
+            return Err(GameError::ResourceLoadError(format!("{} was played with other items", name)));
+        }
+        self.turn_mode = match replay.get("mode") {
+            Some("phases") => TurnMode::Phases,
+            _ => TurnMode::Initiative,
+        };
+        let seed = replay.parse_or("seed", self.seed)?;
+        let mut commands = Vec::new();
+        for &(ref key, ref value) in replay.entries.iter().filter(|entry| entry.0 == "command") {
+            let command = Command::from_text(value)
+                .ok_or_else(|| GameError::ResourceLoadError(format!("replay: unknown {} {}", key, value)))?;
+            // a hand-edited file could point anywhere
+            if let Command::Select(pos) = command {
+                if !map.contains(pos) {
+                    return Err(GameError::ResourceLoadError(format!("replay: {} {} is off the map", key, value)));
+                }
+            }
+            commands.push(command);
+        }
+        if let Some(ref mut campaign) = self.campaign {
+            campaign.stop();
+        }
+        self.restart(map)?;
+        self.seed = seed;
+        if watch {
+            self.replay = commands.into_iter().collect();
+            return Ok(());
+        }
+        for command in commands {
+            self.apply(command);
+            // nobody watches, the rules don't wait for the animations
+            self.events.clear();
+        }
+        Ok(())
+    }
+
+    /// Plays the next command of the replay being watched, once the last one
+    /// was shown. Returns whether there was one.
+    pub fn play_replay(&mut self) -> bool {
+        if self.animations.busy() || !self.events.is_empty() {
+            return false;
+        }
+        match self.replay.pop_front() {
+            Some(command) => {
+                self.apply(command);
+                true
+            },
+            None => false,
+        }
+    }
+}
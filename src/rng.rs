@@ -0,0 +1,45 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A small xorshift generator. Not for cryptography, but the same seed always
+/// gives the same numbers, which is what reproducible maps and matches need.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // xorshift is stuck at 0, and close seeds should not give close numbers
+        let state = seed ^ 0x9E37_79B9_7F4A_7C15;
+        Rng { state: if state == 0 { 1 } else { state } }
+    }
+
+    /// A seed that changes every run, for when none is given
+    pub fn clock_seed() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs() ^ (time.subsec_nanos() as u64) << 32)
+            .unwrap_or(0)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// A number from 0 included to `n` excluded, `n` must not be 0.
+    pub fn below(&mut self, n: u32) -> u32 {
+        (self.next_u64() % n as u64) as u32
+    }
+
+    /// A number from `low` to `high`, both included
+    pub fn range(&mut self, low: u32, high: u32) -> u32 {
+        low + self.below(high - low + 1)
+    }
+
+    /// True with a probability `p`, from 0 to 1
+    pub fn chance(&mut self, p: f32) -> bool {
+        ((self.next_u64() >> 40) as f32 / (1u64 << 24) as f32) < p
+    }
+}
@@ -0,0 +1,224 @@
+use std::fs;
+use std::path::Path;
+
+use ggez::{GameError, GameResult};
+
+use objective::Record;
+
+use super::{data, Character, Class, Direction, GameState, GridPosition, Status, NB_TEAM};
+
+/// The roster we start with when there is no save yet
+pub const DEFAULT_ROSTER: &'static str = include_str!("../resources/roster.txt");
+
+/// Where the roster is saved between matches, in the resources
+pub const SAVE: &'static str = "saves/roster.txt";
+
+const XP_PER_DAMAGE: u32 = 10;
+const XP_PER_KILL: u32 = 50;
+const XP_PER_LEVEL: u32 = 100;
+
+/// A character as it is kept from one match to the next
+#[derive(Debug, Clone)]
+pub struct Unit {
+    pub name: String,
+    pub team: usize,
+    pub class: Class,
+    pub level: u32,
+    /// Experience towards the next level
+    pub xp: u32,
+    pub max_hp: u8,
+    pub mobi: u8,
+    /// Damage added to the attacks of its class
+    pub attack: u8,
+    /// Dead for good, it does not fight anymore
+    pub lost: bool,
+    pub items: Vec<String>,
+}
+
+impl Unit {
+    /// Spends the experience on levels, and tells what each one brought
+    fn level_up(&mut self) -> Vec<String> {
+        let mut messages = Vec::new();
+        while self.xp >= XP_PER_LEVEL {
+            self.xp -= XP_PER_LEVEL;
+            self.level += 1;
+            let mut gains = vec![String::from("+1 HP")];
+            self.max_hp += 1;
+            if self.level % 2 == 0 {
+                self.attack += 1;
+                gains.push(String::from("+1 attack"));
+            }
+            if self.level % 3 == 0 {
+                self.mobi += 1;
+                gains.push(String::from("+1 MP"));
+            }
+            messages.push(format!("{} reaches level {}: {}", self.name, self.level, gains.join(", ")));
+        }
+        messages
+    }
+}
+
+/// Everyone who fights, and whether the dead stay dead
+#[derive(Debug, Clone)]
+pub struct Roster {
+    pub units: Vec<Unit>,
+    /// Dead units are lost for good, instead of coming back for the next match
+    pub permadeath: bool,
+}
+
+impl Roster {
+    pub fn parse(text: &str) -> GameResult<Roster> {
+        Roster::from_sections(&data::parse(text)?)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> GameResult<Roster> {
+        Roster::from_sections(&data::load(path)?)
+    }
+
+    /// `permadeath` before the first section, then the units, in the order
+    /// of the characters.
+    pub fn from_sections(sections: &[data::Section]) -> GameResult<Roster> {
+        let permadeath = match sections.iter().find(|section| section.name.is_empty()) {
+            Some(section) => section.parse_or("permadeath", false)?,
+            None => false,
+        };
+        let roster = Roster { units: units(sections)?, permadeath: permadeath };
+        if roster.units.is_empty() {
+            return Err(GameError::ResourceLoadError(String::from("the roster has no units")));
+        }
+        Ok(roster)
+    }
+
+    /// The file giving back this roster
+    pub fn to_text(&self) -> String {
+        let mut text = format!("permadeath = {}\n", self.permadeath);
+        for unit in self.units.iter() {
+            text.push_str(&format!("\n[{}]\nteam = {}\nclass = {}\nlevel = {}\nxp = {}\nhp = {}\nmobi = {}\nattack = {}\n",
+                                   unit.name, unit.team, unit.class.key(), unit.level, unit.xp, unit.max_hp, unit.mobi, unit.attack));
+            if unit.lost {
+                text.push_str("lost = true\n");
+            }
+            if !unit.items.is_empty() {
+                text.push_str(&format!("items = {}\n", unit.items.join(" ")));
+            }
+        }
+        text
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> GameResult<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_text())?;
+        Ok(())
+    }
+
+    /// The characters of a new match, healed: the whole roster, or against
+    /// `enemies` only its team 0. The lost ones are there but dead, the map
+    /// only places the others.
+    pub fn characters(&self, enemies: Option<&[Unit]>) -> Vec<Character> {
+        let lineup: Vec<(Option<usize>, &Unit)> = match enemies {
+            None => self.units.iter().enumerate().map(|(index, unit)| (Some(index), unit)).collect(),
+            Some(enemies) => self.units.iter().enumerate()
+                .filter(|&(_, unit)| unit.team == 0)
+                .map(|(index, unit)| (Some(index), unit))
+                .chain(enemies.iter().map(|unit| (None, unit)))
+                .collect(),
+        };
+        lineup.into_iter().enumerate().map(|(id, (index, unit))| Character {
+            id: id,
+            unit: index,
+            name: unit.name.clone(),
+            team: unit.team,
+            class: unit.class,
+            level: unit.level,
+            attack: unit.attack,
+            pos: GridPosition { x: 0, y: 0 },
+            mobi: unit.mobi,
+            mp: unit.mobi,
+            facing: if unit.team == 0 { Direction::Down } else { Direction::Up },
+            selector: GridPosition { x: 0, y: 0 },
+            hp: if unit.lost { 0 } else { unit.max_hp },
+            max_hp: unit.max_hp,
+            state: if unit.lost { Status::Dead } else { Status::Alive },
+            recharge: 0,
+            acted: false,
+            inventory: unit.items.clone(),
+            item: 0,
+        }).collect()
+    }
+
+    /// Takes in what happened during a match: experience for the damage
+    /// dealt and the kills, the bags as they are now, and the dead lost or
+    /// back on their feet. Tells what changed.
+    pub fn update(&mut self, characters: &[Character], records: &[Record]) -> Vec<String> {
+        let mut messages = Vec::new();
+        let permadeath = self.permadeath;
+        for (character, record) in characters.iter().zip(records.iter()) {
+            let unit = match character.unit.and_then(|index| self.units.get_mut(index)) {
+                Some(unit) => unit,
+                None => continue,
+            };
+            if unit.lost {
+                continue;
+            }
+            unit.xp += record.damage_dealt * XP_PER_DAMAGE + record.kills * XP_PER_KILL;
+            messages.extend(unit.level_up());
+            unit.items = character.inventory.clone();
+            if !character.is_alive() && permadeath {
+                unit.lost = true;
+                messages.push(format!("{} is lost for good", unit.name));
+            }
+        }
+        for team in 0..NB_TEAM {
+            if self.units.iter().filter(|unit| unit.team == team).all(|unit| unit.lost) {
+                messages.push(format!("Team {} has no one left, remove {} for a new roster", team, SAVE));
+            }
+        }
+        messages
+    }
+}
+
+/// Reads the units of a data file, a section per unit named after it, e.g.
+/// the enemies of a scenario.
+pub fn units(sections: &[data::Section]) -> GameResult<Vec<Unit>> {
+    let mut units = Vec::new();
+    for section in sections.iter().filter(|section| !section.name.is_empty()) {
+        let error = |message: String| GameError::ResourceLoadError(format!("unit {}: {}", section.name, message));
+        let class = section.parse::<String>("class")?;
+        let class = Class::from_key(&class).ok_or_else(|| error(format!("unknown class {}", class)))?;
+        let team = section.parse_or("team", 0)?;
+        if team >= NB_TEAM {
+            return Err(error(format!("there is no team {}", team)));
+        }
+        units.push(Unit {
+            name: section.name.clone(),
+            team: team,
+            class: class,
+            level: section.parse_or("level", 1)?,
+            xp: section.parse_or("xp", 0)?,
+            max_hp: section.parse("hp")?,
+            mobi: section.parse("mobi")?,
+            attack: section.parse_or("attack", 0)?,
+            lost: section.parse_or("lost", false)?,
+            items: section.get("items").unwrap_or("").split_whitespace().map(String::from).collect(),
+        });
+    }
+    Ok(units)
+}
+
+impl GameState {
+    /// Once the game is over, the roster learns what happened and is saved.
+    /// Maps from the editor don't count, they are just being tried out, and
+    /// neither do simulated matches.
+    pub fn finish_match(&mut self) {
+        if self.playtest || self.headless {
+            return;
+        }
+        self.progress = self.roster.update(&self.characters, &self.records);
+        if let Err(e) = self.roster.save(self.resources.join(SAVE)) {
+            error!("Could not save the roster: {}", e);
+        }
+    }
+}
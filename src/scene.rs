@@ -0,0 +1,482 @@
+use std::time::Duration;
+
+use ggez::event::{self, Axis, Button, EventHandler, Keycode, MouseButton, MouseState};
+use ggez::{graphics, timer, Context, GameResult};
+
+use clock::Clock;
+use hud;
+use input::{Command, ControlMode};
+use phase::TurnMode;
+
+use super::{Direction, GameState};
+
+/// What a scene asks of the stack once it handled an event
+pub enum Transition {
+    None,
+    /// Shows a new scene over this one
+    Push(Box<dyn Scene>),
+    /// Back to the scene below
+    Pop,
+    /// Back to the first scene, the title menu
+    Root,
+    Quit,
+}
+
+/// A screen with its own input: the title menu, the game, the pause menu...
+/// Only the scene on top of the stack gets the input.
+pub trait Scene {
+    fn update(&mut self, _ctx: &mut Context, _game: &mut GameState) -> GameResult<Transition> {
+        Ok(Transition::None)
+    }
+
+    fn draw(&mut self, ctx: &mut Context, game: &mut GameState) -> GameResult<()>;
+
+    /// A key or a gamepad button, as a command
+    fn command(&mut self, _game: &mut GameState, _command: Command) -> Transition {
+        Transition::None
+    }
+
+    /// Whether the scene below is drawn under this one
+    fn is_overlay(&self) -> bool {
+        false
+    }
+
+    /// Whether the game gets the input as it comes, instead of commands
+    fn plays(&self) -> bool {
+        false
+    }
+}
+
+/// The scenes on screen, the last one on top, and the game they are about.
+/// This is what ggez runs, it hands each event to the scene on top.
+pub struct SceneStack {
+    pub game: GameState,
+    scenes: Vec<Box<dyn Scene>>,
+}
+
+impl SceneStack {
+    /// Starts on the title menu
+    pub fn new(game: GameState) -> Self {
+        SceneStack { game: game, scenes: vec![Box::new(Title::new())] }
+    }
+
+    /// Skips the title menu, straight to the game
+    pub fn play(&mut self) {
+        self.scenes.push(Box::new(Game));
+    }
+
+    fn push(&mut self, scene: Box<dyn Scene>) {
+        self.scenes.push(scene);
+    }
+
+    fn apply(&mut self, ctx: &mut Context, transition: Transition) {
+        match transition {
+            Transition::None => return,
+            Transition::Push(scene) => self.scenes.push(scene),
+            Transition::Pop => {
+                if self.scenes.len() > 1 {
+                    self.scenes.pop();
+                }
+            },
+            Transition::Root => self.scenes.truncate(1),
+            Transition::Quit => {
+                if let Err(e) = ctx.quit() {
+                    error!("Could not quit: {}", e);
+                }
+            },
+        }
+        // the game stood still under the other scenes
+        if self.playing() {
+            self.game.resume();
+        }
+    }
+
+    fn playing(&self) -> bool {
+        self.scenes.last().map_or(false, |scene| scene.plays())
+    }
+
+    fn command(&mut self, ctx: &mut Context, command: Command) {
+        let transition = match self.scenes.last_mut() {
+            Some(scene) => scene.command(&mut self.game, command),
+            None => return,
+        };
+        self.apply(ctx, transition);
+    }
+}
+
+impl EventHandler for SceneStack {
+    fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let transition = match self.scenes.last_mut() {
+            Some(scene) => scene.update(ctx, &mut self.game)?,
+            None => return Ok(()),
+        };
+        self.apply(ctx, transition);
+        Ok(())
+    }
+
+    /// Draws the scene on top, over the ones below as long as it is an overlay
+    fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+        graphics::clear(ctx);
+        let bottom = self.scenes.iter().rposition(|scene| !scene.is_overlay()).unwrap_or(0);
+        for scene in self.scenes[bottom..].iter_mut() {
+            scene.draw(ctx, &mut self.game)?;
+        }
+        graphics::present(ctx);
+        timer::yield_now();
+        Ok(())
+    }
+
+    /// Escape pauses the game, menus get their keys as commands
+    fn key_down_event(&mut self, ctx: &mut Context, keycode: Keycode, keymod: event::Mod, repeat: bool) {
+        trace!("Key {:?} down", keycode);
+        if self.playing() {
+            if keycode == Keycode::Escape {
+                self.push(Box::new(Pause::new()));
+            } else {
+                self.game.key_down_event(ctx, keycode, keymod, repeat);
+            }
+        } else if let Some(command) = Command::from_keycode(keycode) {
+            self.command(ctx, command);
+        }
+    }
+
+    fn resize_event(&mut self, ctx: &mut Context, width: u32, height: u32) {
+        self.game.resize_event(ctx, width, height);
+    }
+
+    fn mouse_motion_event(&mut self, ctx: &mut Context, state: MouseState, x: i32, y: i32, xrel: i32, yrel: i32) {
+        if self.playing() {
+            self.game.mouse_motion_event(ctx, state, x, y, xrel, yrel);
+        }
+    }
+
+    fn mouse_wheel_event(&mut self, ctx: &mut Context, x: i32, y: i32) {
+        if self.playing() {
+            self.game.mouse_wheel_event(ctx, x, y);
+        }
+    }
+
+    fn mouse_button_down_event(&mut self, ctx: &mut Context, button: MouseButton, x: i32, y: i32) {
+        trace!("Mouse {:?} down at ({}, {})", button, x, y);
+        if self.playing() {
+            self.game.mouse_button_down_event(ctx, button, x, y);
+        }
+    }
+
+    /// The guide button pauses the game
+    fn controller_button_down_event(&mut self, ctx: &mut Context, btn: Button, instance_id: i32) {
+        trace!("Gamepad {} button {:?} down", instance_id, btn);
+        if self.playing() {
+            if btn == Button::Guide {
+                self.push(Box::new(Pause::new()));
+            } else {
+                self.game.controller_button_down_event(ctx, btn, instance_id);
+            }
+        } else if let Some(command) = Command::from_button(btn) {
+            self.command(ctx, command);
+        }
+    }
+
+    fn controller_axis_event(&mut self, ctx: &mut Context, axis: Axis, value: i16, instance_id: i32) {
+        trace!("Gamepad {} axis {:?} at {}", instance_id, axis, value);
+        if self.playing() {
+            self.game.controller_axis_event(ctx, axis, value, instance_id);
+        } else if let Some(command) = self.game.controls.stick(axis, value, instance_id) {
+            self.command(ctx, command);
+        }
+    }
+}
+
+/// A list of entries to pick from with the arrows
+struct Menu {
+    title: String,
+    entries: Vec<String>,
+    cursor: usize,
+}
+
+impl Menu {
+    fn new(title: &str, entries: &[&str]) -> Self {
+        Menu { title: title.to_string(), entries: entries.iter().map(|entry| entry.to_string()).collect(), cursor: 0 }
+    }
+
+    /// Moves the cursor up or down, returns whether the command was an arrow
+    fn step(&mut self, command: &Command) -> bool {
+        match *command {
+            Command::Direction(Direction::Up) => self.cursor = (self.cursor + self.entries.len() - 1) % self.entries.len(),
+            Command::Direction(Direction::Down) => self.cursor = (self.cursor + 1) % self.entries.len(),
+            _ => return false,
+        }
+        true
+    }
+
+    fn lines(&self) -> Vec<String> {
+        let mut lines = vec![self.title.clone(), String::new()];
+        for (index, entry) in self.entries.iter().enumerate() {
+            let marker = if index == self.cursor { ">" } else { " " };
+            lines.push(format!("{} {}", marker, entry));
+        }
+        lines.push(String::new());
+        lines.push(String::from("Arrows: pick, Space / A: select, Escape / B: back"));
+        lines
+    }
+
+    fn draw(&self, ctx: &mut Context, game: &GameState) -> GameResult<()> {
+        hud::draw_centered(ctx, &game.font, &self.lines(), game.camera.window(), game.camera.tile_width() / 8.0)
+    }
+}
+
+/// Whether a command picks the entry under the cursor
+fn selects(command: &Command) -> bool {
+    match *command {
+        Command::Confirm | Command::EndTurn => true,
+        _ => false,
+    }
+}
+
+/// The first scene: start a game, the campaign or the editor
+struct Title {
+    menu: Menu,
+}
+
+impl Title {
+    fn new() -> Self {
+        Title { menu: Menu::new("ASCII War", &["New game", "Load campaign", "Editor", "Settings", "Quit"]) }
+    }
+}
+
+impl Scene for Title {
+    fn draw(&mut self, ctx: &mut Context, game: &mut GameState) -> GameResult<()> {
+        self.menu.draw(ctx, game)
+    }
+
+    fn command(&mut self, game: &mut GameState, command: Command) -> Transition {
+        if self.menu.step(&command) || !selects(&command) {
+            return Transition::None;
+        }
+        match self.menu.cursor {
+            0 => {
+                if let Some(ref mut campaign) = game.campaign {
+                    campaign.stop();
+                }
+                let map = game.home.clone();
+                if let Err(e) = game.restart(map) {
+                    error!("Cannot start a game: {}", e);
+                    return Transition::None;
+                }
+                game.editing = false;
+                Transition::Push(Box::new(Game))
+            },
+            1 => {
+                if game.campaign.is_none() {
+                    warn!("There is no campaign to load");
+                    return Transition::None;
+                }
+                // out of the editor, if the last game left from there
+                game.editing = false;
+                game.camera.set_map_size(&game.map);
+                game.open_missions();
+                Transition::Push(Box::new(Game))
+            },
+            2 => {
+                if !game.editing {
+                    game.toggle_editor();
+                }
+                Transition::Push(Box::new(Game))
+            },
+            3 => Transition::Push(Box::new(Settings::new(false))),
+            _ => Transition::Quit,
+        }
+    }
+}
+
+/// The game itself, with the editor
+struct Game;
+
+impl Scene for Game {
+    /// Once the game is over and the last blow shown, the results come
+    fn update(&mut self, ctx: &mut Context, game: &mut GameState) -> GameResult<Transition> {
+        game.update(ctx)?;
+        let campaign_shown = game.campaign.as_ref().map_or(false, |campaign| campaign.lines().is_some());
+        if game.gameover && !game.editing && !campaign_shown && !game.animations.busy() {
+            return Ok(Transition::Push(Box::new(GameOver)));
+        }
+        Ok(Transition::None)
+    }
+
+    fn draw(&mut self, ctx: &mut Context, game: &mut GameState) -> GameResult<()> {
+        game.draw(ctx)
+    }
+
+    fn plays(&self) -> bool {
+        true
+    }
+}
+
+/// Over the game, which stands still
+struct Pause {
+    menu: Menu,
+}
+
+impl Pause {
+    fn new() -> Self {
+        Pause { menu: Menu::new("Paused", &["Resume", "Restart", "Settings", "Quit to title"]) }
+    }
+}
+
+impl Scene for Pause {
+    fn draw(&mut self, ctx: &mut Context, game: &mut GameState) -> GameResult<()> {
+        hud::draw_shade(ctx, game.camera.window())?;
+        self.menu.draw(ctx, game)
+    }
+
+    fn command(&mut self, game: &mut GameState, command: Command) -> Transition {
+        if let Command::Cancel = command {
+            return Transition::Pop;
+        }
+        if self.menu.step(&command) || !selects(&command) {
+            return Transition::None;
+        }
+        match self.menu.cursor {
+            0 => Transition::Pop,
+            1 => {
+                if let Err(e) = game.restart_same() {
+                    error!("Cannot restart: {}", e);
+                }
+                Transition::Pop
+            },
+            2 => Transition::Push(Box::new(Settings::new(true))),
+            _ => Transition::Root,
+        }
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
+
+/// The results, over the board as the game ended
+struct GameOver;
+
+impl Scene for GameOver {
+    fn draw(&mut self, ctx: &mut Context, game: &mut GameState) -> GameResult<()> {
+        let padding = game.camera.tile_width() / 8.0;
+        hud::draw_centered(ctx, &game.font, &game.results_lines(), game.camera.window(), padding)
+    }
+
+    fn command(&mut self, game: &mut GameState, command: Command) -> Transition {
+        match command {
+            Command::Confirm | Command::EndTurn => {
+                game.play_again();
+                Transition::Pop
+            },
+            Command::Cancel => Transition::Root,
+            _ => Transition::None,
+        }
+    }
+}
+
+/// Turn time limits, in seconds, and chess clocks, in minutes, to pick from
+const TURN_TIMES: [u64; 4] = [0, 15, 30, 60];
+const CLOCKS: [u64; 4] = [0, 5, 10, 20];
+
+/// How the games are played. Changes apply right away, a changed chess
+/// clock starts full.
+struct Settings {
+    cursor: usize,
+    /// Opened from the pause menu: the turns and the time limits the game
+    /// started with stay, they can only change from the title
+    playing: bool,
+}
+
+/// How many settings, from the first, only change between games
+const GAME_SETTINGS: usize = 3;
+
+impl Settings {
+    fn new(playing: bool) -> Self {
+        Settings { cursor: 0, playing: playing }
+    }
+
+    fn menu(&self, game: &GameState) -> Menu {
+        let (turn_limit, total) = game.clock.limits();
+        let turns = match game.turn_mode {
+            TurnMode::Initiative => "initiative",
+            TurnMode::Phases => "team phases",
+        };
+        let turn_time = turn_limit.map_or(String::from("off"), |limit| format!("{} s", limit.as_secs()));
+        let clock = total.map_or(String::from("off"), |total| format!("{} min", total.as_secs() / 60));
+        let gamepads = match game.controls.mode {
+            ControlMode::Shared => "shared",
+            ControlMode::PerTeam => "one per team",
+        };
+        let mut menu = Menu {
+            title: String::from("Settings"),
+            entries: vec![format!("Turns: {}", turns), format!("Turn time: {}", turn_time),
+                          format!("Chess clock: {}", clock), format!("Gamepads: {}", gamepads)],
+            cursor: self.cursor,
+        };
+        if self.playing {
+            for entry in menu.entries[..GAME_SETTINGS].iter_mut() {
+                entry.push_str(" (from the title)");
+            }
+        }
+        menu.entries.push(String::from("Back"));
+        menu
+    }
+
+    /// Moves the setting under the cursor to its next value
+    fn change(&self, game: &mut GameState) {
+        if self.playing && self.cursor < GAME_SETTINGS {
+            return;
+        }
+        let (turn_limit, total) = game.clock.limits();
+        match self.cursor {
+            0 => game.turn_mode = match game.turn_mode {
+                TurnMode::Initiative => TurnMode::Phases,
+                TurnMode::Phases => TurnMode::Initiative,
+            },
+            1 => game.clock = Clock::new(next(&TURN_TIMES, turn_limit, 1), total),
+            2 => game.clock = Clock::new(turn_limit, next(&CLOCKS, total, 60)),
+            3 => game.controls.toggle_mode(),
+            _ => (),
+        }
+    }
+}
+
+/// The value after `current` in `choices`, counted in `unit` seconds, 0 being none
+fn next(choices: &[u64], current: Option<Duration>, unit: u64) -> Option<Duration> {
+    let current = current.map_or(0, |duration| duration.as_secs() / unit);
+    let index = choices.iter().position(|&choice| choice == current).map_or(0, |index| (index + 1) % choices.len());
+    match choices[index] {
+        0 => None,
+        choice => Some(Duration::from_secs(choice * unit)),
+    }
+}
+
+impl Scene for Settings {
+    fn draw(&mut self, ctx: &mut Context, game: &mut GameState) -> GameResult<()> {
+        self.menu(game).draw(ctx, game)
+    }
+
+    fn command(&mut self, game: &mut GameState, command: Command) -> Transition {
+        let mut menu = self.menu(game);
+        if menu.step(&command) {
+            self.cursor = menu.cursor;
+            return Transition::None;
+        }
+        match command {
+            Command::Cancel => Transition::Pop,
+            Command::Direction(Direction::Left) | Command::Direction(Direction::Right) => {
+                self.change(game);
+                Transition::None
+            },
+            ref command if selects(command) => {
+                if self.cursor == menu.entries.len() - 1 {
+                    return Transition::Pop;
+                }
+                self.change(game);
+                Transition::None
+            },
+            _ => Transition::None,
+        }
+    }
+}
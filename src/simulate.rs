@@ -0,0 +1,189 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ggez::GameResult;
+
+use ai::{self, Controller};
+use input::Command;
+use item::Items;
+use phase::TurnMode;
+use roster::Roster;
+
+use super::{GameState, Map, NB_TEAM};
+
+/// A character that keeps getting commands without ending its turn is made to
+/// end it, so a stuck AI cannot hang the batch
+const MAX_COMMANDS: u32 = 100;
+
+/// Batch matches between AI controllers, without a window, to balance the
+/// classes and the maps.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub matches: u32,
+    /// The controller of each team
+    pub ai: Vec<String>,
+    /// Where the statistics go, as JSON if the file ends in `.json` and as
+    /// CSV otherwise. On the standard output as CSV when there is none.
+    pub stats: Option<PathBuf>,
+    /// A match still going after that many turns is a draw
+    pub max_turns: u32,
+    pub seed: u64,
+    pub turn_mode: TurnMode,
+}
+
+/// What a character did, summed over the matches
+#[derive(Debug, Clone, Default)]
+struct UnitStats {
+    name: String,
+    team: usize,
+    class: &'static str,
+    damage_dealt: u32,
+    damage_taken: u32,
+    kills: u32,
+    survived: u32,
+}
+
+/// What happened over all the matches
+#[derive(Debug, Clone)]
+pub struct Stats {
+    controllers: Vec<String>,
+    matches: u32,
+    wins: Vec<u32>,
+    /// Nobody won: everybody died, or the turns ran out
+    draws: u32,
+    turns: u32,
+    commands: u32,
+    units: Vec<UnitStats>,
+}
+
+impl Stats {
+    fn average(&self, total: u32) -> f32 {
+        if self.matches == 0 { 0.0 } else { total as f32 / self.matches as f32 }
+    }
+
+    /// Commands given per turn, the moves and the aiming included
+    fn turn_length(&self) -> f32 {
+        if self.turns == 0 { 0.0 } else { self.commands as f32 / self.turns as f32 }
+    }
+
+    /// A table of the matches, one of the teams and one of the units,
+    /// separated by empty lines
+    pub fn to_csv(&self) -> String {
+        let mut text = String::from("matches,draws,average_turns,average_turn_length\n");
+        text.push_str(&format!("{},{},{:.2},{:.2}\n\n", self.matches, self.draws, self.average(self.turns), self.turn_length()));
+        text.push_str("team,controller,wins,losses,draws\n");
+        for (team, controller) in self.controllers.iter().enumerate() {
+            let losses = self.matches - self.draws - self.wins[team];
+            text.push_str(&format!("{},{},{},{},{}\n", team, controller, self.wins[team], losses, self.draws));
+        }
+        text.push_str("\nunit,team,class,average_damage_dealt,average_damage_taken,average_kills,survival_rate\n");
+        for unit in self.units.iter() {
+            text.push_str(&format!("{},{},{},{:.2},{:.2},{:.2},{:.2}\n",
+                                   unit.name, unit.team, unit.class, self.average(unit.damage_dealt),
+                                   self.average(unit.damage_taken), self.average(unit.kills), self.average(unit.survived)));
+        }
+        text
+    }
+
+    pub fn to_json(&self) -> String {
+        let teams: Vec<String> = self.controllers.iter().enumerate().map(|(team, controller)| {
+            format!("    {{\"team\": {}, \"controller\": \"{}\", \"wins\": {}, \"losses\": {}, \"draws\": {}}}",
+                    team, controller, self.wins[team], self.matches - self.draws - self.wins[team], self.draws)
+        }).collect();
+        let units: Vec<String> = self.units.iter().map(|unit| {
+            format!("    {{\"name\": \"{}\", \"team\": {}, \"class\": \"{}\", \"average_damage_dealt\": {:.2}, \
+                     \"average_damage_taken\": {:.2}, \"average_kills\": {:.2}, \"survival_rate\": {:.2}}}",
+                    unit.name.replace('\\', "\\\\").replace('"', "\\\""), unit.team, unit.class, self.average(unit.damage_dealt),
+                    self.average(unit.damage_taken), self.average(unit.kills), self.average(unit.survived))
+        }).collect();
+        format!("{{\n  \"matches\": {},\n  \"draws\": {},\n  \"average_turns\": {:.2},\n  \"average_turn_length\": {:.2},\n  \
+                 \"teams\": [\n{}\n  ],\n  \"units\": [\n{}\n  ]\n}}\n",
+                self.matches, self.draws, self.average(self.turns), self.turn_length(), teams.join(",\n"), units.join(",\n"))
+    }
+
+    /// Writes them where the settings say
+    pub fn write(&self, settings: &Settings) -> GameResult<()> {
+        match settings.stats {
+            Some(ref path) => {
+                let json = path.extension().map_or(false, |extension| extension == "json");
+                fs::write(path, if json { self.to_json() } else { self.to_csv() })?;
+            },
+            None => print!("{}", self.to_csv()),
+        }
+        Ok(())
+    }
+}
+
+/// Plays the matches on `map`, with the items and the starting roster of
+/// `resources`. Nothing is saved.
+pub fn run(settings: &Settings, map: Map, resources: &Path) -> GameResult<Stats> {
+    let mut game = GameState::new(map)?;
+    game.headless = true;
+    game.turn_mode = settings.turn_mode;
+    game.resources = resources.to_path_buf();
+    game.items = Items::load(resources.join("items.txt"))?;
+    game.roster = Roster::load(resources.join("roster.txt"))?;
+    let mut stats = Stats {
+        controllers: settings.ai.clone(),
+        matches: 0,
+        wins: vec![0; NB_TEAM],
+        draws: 0,
+        turns: 0,
+        commands: 0,
+        units: Vec::new(),
+    };
+    for index in 0..settings.matches {
+        // a different seed per match, so random controllers don't replay the same one
+        let seed = settings.seed.wrapping_add(index as u64);
+        let mut controllers: Vec<Box<dyn Controller>> = settings.ai.iter().enumerate()
+            .map(|(team, name)| ai::controller(name, seed.wrapping_mul(NB_TEAM as u64).wrapping_add(team as u64)).unwrap())
+            .collect();
+        let start = game.start.clone();
+        game.restart(start)?;
+        stats.commands += play(&mut game, &mut controllers, settings.max_turns);
+        stats.matches += 1;
+        stats.turns += game.turn;
+        match game.outcome.as_ref().and_then(|outcome| outcome.winner) {
+            Some(team) => stats.wins[team] += 1,
+            None => stats.draws += 1,
+        }
+        for (id, (character, record)) in game.characters.iter().zip(game.records.iter()).enumerate() {
+            if stats.units.len() <= id {
+                stats.units.push(UnitStats {
+                    name: character.name.clone(),
+                    team: character.team,
+                    class: character.class.key(),
+                    ..UnitStats::default()
+                });
+            }
+            let unit = &mut stats.units[id];
+            unit.damage_dealt += record.damage_dealt;
+            unit.damage_taken += record.damage_taken;
+            unit.kills += record.kills;
+            if character.is_alive() {
+                unit.survived += 1;
+            }
+        }
+    }
+    Ok(stats)
+}
+
+/// Plays one match to its end, or until the turns run out. Returns how many
+/// commands it took.
+pub fn play(game: &mut GameState, controllers: &mut [Box<dyn Controller>], max_turns: u32) -> u32 {
+    let mut commands = 0;
+    while !game.gameover && game.turn < max_turns {
+        let turn = game.turn;
+        let mut given = 0;
+        while !game.gameover && game.turn == turn {
+            let team = game.characters[game.char_id].team;
+            let command = if given < MAX_COMMANDS { controllers[team].next_command(game) } else { Command::EndTurn };
+            game.apply(command);
+            // nobody watches, the rules don't wait for the animations
+            game.events.clear();
+            given += 1;
+        }
+        commands += given;
+    }
+    commands
+}
@@ -0,0 +1,505 @@
+use std::{env, fs, process};
+use std::path::Path;
+use std::time::Duration;
+
+use ai::Controller;
+use camera::Camera;
+use campaign::Campaign;
+use clock::Clock;
+use input::Command;
+use item::Items;
+use rng::Rng;
+use roster::Roster;
+use simulate;
+
+use super::{data, mapfile, Action, Direction, Event, GameState, GridPosition, Map, Status, TileState, NO_CHAR, TRAP_DAMAGE, TRAP_REARM};
+
+/// Hilda, the fighter of team 0, and Wren, the archer of team 1, as in the
+/// built-in roster
+const FIGHTER: usize = 0;
+const ARCHER: usize = 1;
+
+/// A map of `rows`, the fighter on `fighter` and the archer on `archer`
+fn map(rows: &[&str], fighter: (u16, u16), archer: (u16, u16)) -> Map {
+    let mut text = String::from("[map]\n");
+    for row in rows {
+        text.push_str(&format!("row = {}\n", row));
+    }
+    text.push_str(&format!("[spawns]\n0 = {} {}\n1 = {} {}\n", fighter.0, fighter.1, archer.0, archer.1));
+    Map::parse(&text).unwrap()
+}
+
+/// A game on `map` where `id` plays
+fn game(map: Map, id: usize) -> GameState {
+    let mut game = GameState::new(map).unwrap();
+    game.headless = true;
+    game.char_id = id;
+    game
+}
+
+/// Applies a command as if the animations were done with the last one
+fn play(game: &mut GameState, command: Command) {
+    game.apply(command);
+    game.events.clear();
+}
+
+fn pos(x: u16, y: u16) -> GridPosition {
+    GridPosition { x: x, y: y }
+}
+
+#[test]
+fn index_goes_row_by_row() {
+    assert_eq!(pos(0, 0).as_index(5), 0);
+    assert_eq!(pos(4, 0).as_index(5), 4);
+    assert_eq!(pos(0, 1).as_index(5), 5);
+    assert_eq!(pos(3, 2).as_index(5), 13);
+    let map = map(&["....", "....", "...."], (0, 0), (3, 2));
+    assert_eq!(pos(3, 2).as_index(map.width), map.tiles.len() - 1);
+}
+
+#[test]
+fn no_step_off_the_edges() {
+    let map = map(&["...", "...", "..."], (1, 1), (2, 2));
+    assert_eq!(map.step(pos(0, 0), &Direction::Up), None);
+    assert_eq!(map.step(pos(0, 0), &Direction::Left), None);
+    assert_eq!(map.step(pos(2, 2), &Direction::Right), None);
+    assert_eq!(map.step(pos(2, 2), &Direction::Down), None);
+    assert_eq!(map.step(pos(0, 0), &Direction::Right), Some(pos(1, 0)));
+    assert_eq!(map.step(pos(2, 2), &Direction::Up), Some(pos(2, 1)));
+}
+
+#[test]
+fn walls_water_and_others_are_not_available() {
+    let game = game(map(&["#.~", "...", "..."], (1, 1), (2, 1)), FIGHTER);
+    let map = &game.map;
+    assert!(!map.is_available(pos(0, 1), &Direction::Up, true));
+    assert!(map.is_available(pos(1, 1), &Direction::Up, false));
+    // only swimmers go in the water
+    assert!(!map.is_available(pos(2, 1), &Direction::Up, false));
+    assert!(map.is_available(pos(1, 0), &Direction::Right, true));
+    // nor on someone else
+    assert!(!map.is_available(pos(1, 1), &Direction::Right, true));
+    assert!(!map.is_available(pos(0, 0), &Direction::Left, true));
+}
+
+#[test]
+fn moving_spends_mp_and_moves_the_occupant() {
+    let mut game = game(map(&["....", ".,..", "...."], (0, 1), (3, 2)), FIGHTER);
+    let mobi = game.characters[FIGHTER].mobi;
+    play(&mut game, Command::Direction(Direction::Up));
+    assert_eq!(game.characters[FIGHTER].pos, pos(0, 0));
+    assert_eq!(game.characters[FIGHTER].mp, mobi - 1);
+    assert_eq!(game.map.tile(pos(0, 1)).char_id, NO_CHAR);
+    assert_eq!(game.map.tile(pos(0, 0)).char_id, FIGHTER);
+    // difficult ground costs 2, more than what is left
+    play(&mut game, Command::Direction(Direction::Right));
+    play(&mut game, Command::Direction(Direction::Down));
+    assert_eq!(game.characters[FIGHTER].pos, pos(1, 0));
+    assert_eq!(game.characters[FIGHTER].mp, mobi - 2);
+    play(&mut game, Command::EndTurn);
+    game.char_id = FIGHTER;
+    play(&mut game, Command::Direction(Direction::Down));
+    assert_eq!(game.characters[FIGHTER].pos, pos(1, 1));
+    assert_eq!(game.characters[FIGHTER].mp, mobi - 2);
+}
+
+#[test]
+fn blocked_moves_are_free() {
+    let mut game = game(map(&["#..", "...", "..."], (0, 1), (1, 1)), FIGHTER);
+    let mp = game.characters[FIGHTER].mp;
+    play(&mut game, Command::Direction(Direction::Up));
+    play(&mut game, Command::Direction(Direction::Left));
+    play(&mut game, Command::Direction(Direction::Right));
+    assert_eq!(game.characters[FIGHTER].pos, pos(0, 1));
+    assert_eq!(game.characters[FIGHTER].mp, mp);
+    // but the character still turns
+    assert_eq!(game.characters[FIGHTER].facing, Direction::Right);
+}
+
+#[test]
+fn ending_the_turn_gives_the_mp_back() {
+    let mut game = game(map(&["....", "....", "...."], (0, 0), (3, 2)), FIGHTER);
+    play(&mut game, Command::Direction(Direction::Right));
+    play(&mut game, Command::Direction(Direction::Right));
+    assert!(game.characters[FIGHTER].mp < game.characters[FIGHTER].mobi);
+    play(&mut game, Command::EndTurn);
+    assert_eq!(game.characters[FIGHTER].mp, game.characters[FIGHTER].mobi);
+    assert_eq!(game.turn, 1);
+    assert_eq!(game.action, Action::Move);
+}
+
+#[test]
+fn out_of_mp_means_aiming() {
+    let mut game = game(map(&["....", "....", "...."], (0, 0), (3, 2)), FIGHTER);
+    let mobi = game.characters[FIGHTER].mobi;
+    for _ in 0..mobi {
+        play(&mut game, Command::Direction(Direction::Right));
+    }
+    assert_eq!(game.characters[FIGHTER].mp, 0);
+    // the next command already aims, moving the selector and not the character
+    play(&mut game, Command::Direction(Direction::Down));
+    assert_eq!(game.action, Action::Attack);
+    assert_eq!(game.characters[FIGHTER].pos, pos(mobi as u16, 0));
+    assert_eq!(game.characters[FIGHTER].selector, pos(mobi as u16, 1));
+}
+
+#[test]
+fn turns_go_round() {
+    let mut game = GameState::new(map(&["....", "....", "...."], (0, 0), (3, 2))).unwrap();
+    game.headless = true;
+    let mut turns = [0; 2];
+    for turn in 0..40 {
+        assert_eq!(game.turn, turn);
+        turns[game.char_id] += 1;
+        play(&mut game, Command::EndTurn);
+    }
+    assert!(turns[FIGHTER] > 0 && turns[ARCHER] > 0);
+    // archers are faster
+    assert!(turns[ARCHER] > turns[FIGHTER]);
+}
+
+#[test]
+fn dead_characters_lose_their_turns() {
+    let mut game = GameState::new(map(&["....", "....", "...."], (0, 0), (3, 2))).unwrap();
+    game.headless = true;
+    game.hurt(ARCHER, 100, None);
+    for _ in 0..10 {
+        play(&mut game, Command::EndTurn);
+        assert_eq!(game.char_id, FIGHTER);
+    }
+}
+
+#[test]
+fn traps_hurt_then_rearm() {
+    let mut game = game(map(&[".^..", "....", "...."], (0, 0), (3, 2)), FIGHTER);
+    let hp = game.characters[FIGHTER].hp;
+    play(&mut game, Command::Direction(Direction::Right));
+    assert_eq!(game.characters[FIGHTER].hp, hp - TRAP_DAMAGE);
+    assert_eq!(game.map.tile(pos(1, 0)).cooldown, TRAP_REARM);
+    // not armed anymore
+    play(&mut game, Command::Direction(Direction::Left));
+    play(&mut game, Command::Direction(Direction::Right));
+    assert_eq!(game.characters[FIGHTER].hp, hp - TRAP_DAMAGE);
+    // each turn brings it closer to be armed again
+    play(&mut game, Command::EndTurn);
+    assert_eq!(game.map.tile(pos(1, 0)).cooldown, TRAP_REARM - 1);
+}
+
+#[test]
+fn fighters_hit_their_neighbours() {
+    let mut game = game(map(&["....", "....", "...."], (0, 0), (1, 0)), FIGHTER);
+    let damage = game.characters[FIGHTER].damage();
+    let hp = game.characters[ARCHER].hp;
+    play(&mut game, Command::Confirm);
+    play(&mut game, Command::Direction(Direction::Right));
+    play(&mut game, Command::Confirm);
+    assert_eq!(game.characters[ARCHER].hp, hp - damage);
+    assert_eq!(game.records[FIGHTER].damage_dealt, damage as u32);
+    assert_eq!(game.records[ARCHER].damage_taken, damage as u32);
+    // attacking ends the turn
+    assert_eq!(game.turn, 1);
+}
+
+#[test]
+fn attacks_stop_at_their_range_and_at_walls() {
+    // too far for a fighter
+    let mut game = game(map(&["....", "....", "...."], (0, 0), (2, 0)), FIGHTER);
+    let hp = game.characters[ARCHER].hp;
+    play(&mut game, Command::Confirm);
+    play(&mut game, Command::Select(pos(2, 0)));
+    game.apply(Command::Confirm);
+    assert_eq!(game.characters[ARCHER].hp, hp);
+    // nor shown
+    assert!(!game.events.iter().any(|event| match *event {
+        Event::Attacked { .. } => true,
+        _ => false,
+    }));
+    // in range of an archer, but behind a wall
+    let mut game = self::game(map(&[".#..", "....", "...."], (2, 0), (0, 0)), ARCHER);
+    let hp = game.characters[FIGHTER].hp;
+    play(&mut game, Command::Confirm);
+    play(&mut game, Command::Select(pos(2, 0)));
+    play(&mut game, Command::Confirm);
+    assert_eq!(game.characters[FIGHTER].hp, hp);
+    // and around it
+    let mut game = self::game(map(&[".#..", "....", "...."], (0, 2), (0, 0)), ARCHER);
+    play(&mut game, Command::Confirm);
+    play(&mut game, Command::Select(pos(0, 2)));
+    play(&mut game, Command::Confirm);
+    assert_eq!(game.characters[FIGHTER].hp, hp - game.characters[ARCHER].damage());
+}
+
+#[test]
+fn the_dead_leave_their_tile() {
+    let mut game = game(map(&["....", "....", "...."], (0, 0), (1, 0)), FIGHTER);
+    game.characters[ARCHER].hp = 1;
+    play(&mut game, Command::Confirm);
+    play(&mut game, Command::Direction(Direction::Right));
+    play(&mut game, Command::Confirm);
+    assert!(!game.characters[ARCHER].is_alive());
+    assert_eq!(game.map.tile(pos(1, 0)).char_id, NO_CHAR);
+    assert_eq!(game.records[FIGHTER].kills, 1);
+    assert!(game.gameover);
+}
+
+/// Every occupied tile is where its character stands, and every living
+/// character stands on a tile that says so.
+fn check_occupancy(game: &GameState) {
+    for (index, tile) in game.map.tiles.iter().enumerate() {
+        if tile.char_id == NO_CHAR {
+            continue;
+        }
+        let character = &game.characters[tile.char_id];
+        assert!(character.is_alive(), "dead {} still on tile {}", character.name, index);
+        assert_eq!(character.pos.as_index(game.map.width), index, "{} is not on tile {}", character.name, index);
+    }
+    for character in game.characters.iter().filter(|character| character.is_alive()) {
+        assert_eq!(game.map.tile(character.pos).char_id, character.id, "{} is not on its tile", character.name);
+    }
+}
+
+/// Any command at all, with a selected tile on the map
+fn random_command(rng: &mut Rng, map: &Map) -> Command {
+    match rng.below(12) {
+        0 => Command::Confirm,
+        1 => Command::Cancel,
+        2 => Command::EndTurn,
+        3 => Command::Interact,
+        4 => Command::Ability,
+        5 => Command::UseItem,
+        6 => Command::NextItem,
+        7 => Command::Select(pos(rng.below(map.width as u32) as u16, rng.below(map.height as u32) as u16)),
+        _ => Command::Direction([Direction::Up, Direction::Down, Direction::Left, Direction::Right][rng.below(4) as usize]),
+    }
+}
+
+#[test]
+fn tiles_and_characters_agree_after_random_commands() {
+    let maps = [
+        Map::parse(mapfile::DEFAULT_MAP).unwrap(),
+        map(&["..^..~..", ".+,:#%..", "..~~^...", "./..*..^"], (0, 0), (7, 3)),
+    ];
+    for seed in 0..50 {
+        let mut rng = Rng::new(seed);
+        let start = maps[seed as usize % maps.len()].clone();
+        let mut game = GameState::new(start.clone()).unwrap();
+        game.headless = true;
+        check_occupancy(&game);
+        for _ in 0..300 {
+            if game.gameover {
+                game.restart(start.clone()).unwrap();
+            }
+            let command = random_command(&mut rng, &game.map);
+            play(&mut game, command);
+            check_occupancy(&game);
+            for character in game.characters.iter() {
+                assert!(character.hp <= character.max_hp);
+                assert!(character.mp <= character.mobi);
+            }
+            assert!(game.characters[game.char_id].is_alive() || game.gameover);
+        }
+    }
+}
+
+#[test]
+fn drift_between_tiles_and_characters_is_caught() {
+    let mut game = game(map(&["....", "....", "...."], (0, 0), (3, 2)), FIGHTER);
+    assert!(game.inconsistencies().is_empty());
+    // the tile forgets who stands on it
+    game.map.tile_mut(pos(0, 0)).char_id = NO_CHAR;
+    assert_eq!(game.inconsistencies().len(), 1);
+    // and another one remembers someone who left
+    game.map.tile_mut(pos(1, 0)).char_id = ARCHER;
+    assert_eq!(game.inconsistencies().len(), 2);
+    game.map.tile_mut(pos(0, 0)).char_id = FIGHTER;
+    game.map.tile_mut(pos(1, 0)).char_id = NO_CHAR;
+    assert!(game.inconsistencies().is_empty());
+    // dead with hit points, and playing
+    game.characters[FIGHTER].state = Status::Dead;
+    assert!(game.inconsistencies().len() >= 2);
+}
+
+#[test]
+fn commands_read_back_as_written() {
+    let mut rng = Rng::new(7);
+    let map = Map::parse(mapfile::DEFAULT_MAP).unwrap();
+    for _ in 0..100 {
+        let command = random_command(&mut rng, &map);
+        assert_eq!(Command::from_text(&command.to_text()).map(|read| read.to_text()), Some(command.to_text()));
+    }
+    assert!(Command::from_text("jump").is_none());
+}
+
+#[test]
+fn a_loaded_game_goes_on_where_it_was_saved() {
+    let start = Map::parse(mapfile::DEFAULT_MAP).unwrap();
+    let mut game = GameState::new(start.clone()).unwrap();
+    game.headless = true;
+    let mut rng = Rng::new(3);
+    while game.history.len() < 200 && !game.gameover {
+        let command = random_command(&mut rng, &game.map);
+        play(&mut game, command);
+    }
+    let path = env::temp_dir().join(format!("replay-test-{}.txt", process::id()));
+    game.save_game(&path).unwrap();
+    let mut loaded = GameState::new(start).unwrap();
+    loaded.headless = true;
+    loaded.load_game(&path, false).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.turn, game.turn);
+    assert_eq!(loaded.char_id, game.char_id);
+    for (a, b) in loaded.characters.iter().zip(game.characters.iter()) {
+        assert_eq!((a.pos, a.hp, a.mp), (b.pos, b.hp, b.mp));
+    }
+    assert_eq!(loaded.map.to_text(), game.map.to_text());
+}
+
+#[test]
+fn a_save_from_another_roster_is_refused() {
+    let start = Map::parse(mapfile::DEFAULT_MAP).unwrap();
+    let mut game = GameState::new(start.clone()).unwrap();
+    game.seed = 42;
+    play(&mut game, Command::EndTurn);
+    let path = env::temp_dir().join(format!("replay-roster-{}.txt", process::id()));
+    game.save_game(&path).unwrap();
+    let mut loaded = GameState::new(start).unwrap();
+    loaded.roster.units[0].xp += 10;
+    let refused = loaded.load_game(&path, false);
+    let mut same = GameState::new(Map::parse(mapfile::DEFAULT_MAP).unwrap()).unwrap();
+    let accepted = same.load_game(&path, false);
+    fs::remove_file(&path).unwrap();
+    assert!(refused.is_err());
+    assert!(accepted.is_ok());
+    assert_eq!(same.seed, 42);
+}
+
+#[test]
+fn the_hovered_tile_is_forgotten_with_the_map() {
+    let mut game = GameState::new(Map::parse(mapfile::DEFAULT_MAP).unwrap()).unwrap();
+    game.hovered = Some(pos(15, 15));
+    game.restart(map(&["...", "...", "..."], (0, 0), (2, 2))).unwrap();
+    assert_eq!(game.hovered, None);
+    assert!(game.map.contains(pos(2, 2)));
+    assert!(!game.map.contains(pos(3, 0)));
+    assert!(!game.map.contains(pos(0, 3)));
+}
+
+#[test]
+fn a_save_selecting_off_the_map_is_refused() {
+    let path = env::temp_dir().join(format!("replay-off-map-{}.txt", process::id()));
+    let mut game = game(map(&["...", "...", "..."], (0, 0), (2, 2)), FIGHTER);
+    let text = game.replay_text().replace("[replay]\n", "[replay]\ncommand = select 3 1\n");
+    fs::write(&path, text).unwrap();
+    let loaded = game.load_game(&path, false);
+    fs::remove_file(&path).unwrap();
+    assert!(loaded.is_err());
+}
+
+#[test]
+fn healing_stops_at_the_maximum_and_is_shown() {
+    let mut game = game(map(&["...", "...", "..."], (0, 0), (2, 2)), FIGHTER);
+    game.items = Items::parse("[elixir]\neffect = heal\npower = 255\nrange = 0\n").unwrap();
+    let max_hp = game.characters[FIGHTER].max_hp;
+    game.characters[FIGHTER].hp = max_hp - 1;
+    game.characters[FIGHTER].inventory = vec![String::from("elixir")];
+    assert!(game.use_item());
+    assert_eq!(game.characters[FIGHTER].hp, max_hp);
+    assert!(game.events.iter().any(|event| match *event {
+        Event::Healed { id } => id == FIGHTER,
+        _ => false,
+    }));
+}
+
+#[test]
+fn any_number_of_characters_can_fight() {
+    let mut game = game(map(&["....", "....", "...."], (0, 0), (3, 2)), FIGHTER);
+    game.roster = Roster::parse("[A]\nteam = 0\nclass = fighter\nhp = 5\nmobi = 3\n\
+                                 [B]\nteam = 0\nclass = archer\nhp = 5\nmobi = 3\n\
+                                 [C]\nteam = 1\nclass = fighter\nhp = 5\nmobi = 3\n").unwrap();
+    let start = game.start.clone();
+    game.restart(start.clone()).unwrap();
+    assert_eq!(game.characters.len(), 3);
+    assert_eq!(game.timeline.forecast(&game.characters, 3).len(), 3);
+    check_occupancy(&game);
+    // but every team needs someone
+    game.roster = Roster::parse("[A]\nteam = 0\nclass = fighter\nhp = 5\nmobi = 3\n").unwrap();
+    assert!(game.restart(start).is_err());
+}
+
+#[test]
+fn every_scenario_starts() {
+    let resources = Path::new(env!("CARGO_MANIFEST_DIR")).join("resources");
+    let mut game = game(Map::parse(mapfile::DEFAULT_MAP).unwrap(), FIGHTER);
+    game.resources = resources.clone();
+    let sections = data::load(resources.join("campaign.txt")).unwrap();
+    game.campaign = Some(Campaign::load(resources.join("campaign.txt")).unwrap());
+    let count = sections.iter().filter(|section| !section.name.is_empty()).count();
+    for number in 1..count + 1 {
+        game.play_scenario(&number.to_string()).unwrap();
+        check_occupancy(&game);
+    }
+    // the crossing has two guards
+    game.play_scenario("The crossing").unwrap();
+    assert_eq!(game.characters.iter().filter(|character| character.team == 1).count(), 2);
+}
+
+#[test]
+fn the_view_scrolls_at_the_border_of_the_board_only() {
+    let rows = vec!["................................"; 32];
+    let big = map(&rows, (0, 0), (31, 31));
+    // a wide window: the board is 512 pixels wide, between black bars of 144
+    let mut camera = Camera::new(800.0, 512.0, &big);
+    let start = camera.visible();
+    camera.set_mouse(795, 256);
+    for _ in 0..20 {
+        camera.update();
+    }
+    assert_eq!(camera.visible(), start);
+    camera.set_mouse(650, 256);
+    for _ in 0..20 {
+        camera.update();
+    }
+    assert!(camera.visible().0 > start.0);
+}
+
+/// Never ends its turn, nor does anything else
+struct Stuck;
+
+impl Controller for Stuck {
+    fn next_command(&mut self, _game: &GameState) -> Command {
+        Command::Cancel
+    }
+}
+
+#[test]
+fn a_stuck_ai_is_made_to_end_its_turns() {
+    let mut game = game(map(&["....", "....", "...."], (0, 0), (3, 2)), FIGHTER);
+    let mut controllers: Vec<Box<dyn Controller>> = vec![Box::new(Stuck), Box::new(Stuck)];
+    simulate::play(&mut game, &mut controllers, 3);
+    assert_eq!(game.turn, 3);
+}
+
+#[test]
+fn the_editor_opens_the_map_as_it_was_loaded() {
+    let mut game = game(map(&["....", "....", "...."], (0, 0), (3, 2)), FIGHTER);
+    // as if an ability had raised a wall for a few turns
+    game.map.tile_mut(pos(1, 1)).state = TileState::Wall;
+    game.map.temporary.push((pos(1, 1), TileState::Empty, 3));
+    game.toggle_editor();
+    let editor = game.editor.as_ref().unwrap();
+    assert_eq!(editor.map.tile(pos(1, 1)).state, TileState::Empty);
+    assert!(editor.map.tiles.iter().all(|tile| tile.char_id == NO_CHAR));
+}
+
+#[test]
+fn an_empty_chess_clock_loses_the_game() {
+    let mut game = game(map(&["....", "....", "...."], (0, 0), (3, 2)), FIGHTER);
+    game.clock = Clock::new(None, Some(Duration::from_secs(60)));
+    game.clock.tick(Duration::from_secs(30), 0);
+    assert!(!game.clock.flag_fallen(0));
+    game.clock.tick(Duration::from_secs(31), 0);
+    assert!(game.clock.flag_fallen(0) && !game.clock.flag_fallen(1));
+    game.lose_on_time(0);
+    assert!(game.gameover);
+    assert_eq!(game.outcome.as_ref().and_then(|outcome| outcome.winner), Some(1));
+}
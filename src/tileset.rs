@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use ggez::graphics::spritebatch::SpriteBatch;
+use ggez::{graphics, Context, GameError, GameResult};
+
+use animation::Animations;
+use camera::Camera;
+
+use super::{data, Character, Class, Direction, GridPosition, Map, TileState, NB_TEAM, RES_X};
+
+/// What the sprites of each team are multiplied with, light enough to keep
+/// their details
+const TEAM_TINTS: [[f32; 4]; NB_TEAM] = [[0.6, 0.75, 1.0, 1.0], [1.0, 0.85, 0.55, 1.0]];
+
+/// Texture atlas for the board, described by `tileset.txt` in the resource
+/// directory. All the sprites go through one sprite batch per frame.
+pub struct Tileset {
+    batch: SpriteBatch,
+    /// Size of a cell of the atlas, in pixels
+    tile_size: f32,
+    /// Size of the atlas image, in pixels
+    width: f32,
+    height: f32,
+    /// Atlas cell of each kind of tile
+    tiles: HashMap<String, (u16, u16)>,
+    /// Atlas cell of the first of the four facings (up, down, left, right) of each class
+    characters: HashMap<String, (u16, u16)>,
+}
+
+impl Tileset {
+    pub fn load(ctx: &mut Context, dir: &Path) -> GameResult<Tileset> {
+        let sections = data::load(dir.join("tileset.txt"))?;
+        let image = graphics::Image::new(ctx, sections[0].parse::<String>("image")?)?;
+        let mut tiles = HashMap::new();
+        let mut characters = HashMap::new();
+        for section in sections.iter() {
+            let cells = match section.name.as_str() {
+                "tiles" => &mut tiles,
+                "characters" => &mut characters,
+                _ => continue,
+            };
+            for &(ref key, ref value) in section.entries.iter() {
+                cells.insert(key.clone(), data::pair(value)?);
+            }
+        }
+        // better to fall back to plain colors than to draw half the board
+        for state in TileState::ALL.iter() {
+            if !tiles.contains_key(state.key()) {
+                return Err(GameError::ResourceLoadError(format!("tileset has no `{}` tile", state.key())));
+            }
+        }
+        for class in [Class::Fighter, Class::Archer].iter() {
+            if !characters.contains_key(class.key()) {
+                return Err(GameError::ResourceLoadError(format!("tileset has no `{}` character", class.key())));
+            }
+        }
+        Ok(Tileset {
+            tile_size: sections[0].parse_or("tile_size", RES_X as f32)?,
+            width: image.width() as f32,
+            height: image.height() as f32,
+            batch: SpriteBatch::new(image),
+            tiles: tiles,
+            characters: characters,
+        })
+    }
+
+    /// Adds the atlas cell `cell` to the batch, stretched over `rect` on screen.
+    fn add(&mut self, cell: (u16, u16), rect: graphics::Rect, tint: Option<graphics::Color>) {
+        self.batch.add(graphics::DrawParam {
+            src: graphics::Rect::new(
+                cell.0 as f32 * self.tile_size / self.width,
+                cell.1 as f32 * self.tile_size / self.height,
+                self.tile_size / self.width,
+                self.tile_size / self.height,
+            ),
+            dest: graphics::Point2::new(rect.x, rect.y),
+            scale: graphics::Point2::new(rect.w / self.tile_size, rect.h / self.tile_size),
+            color: tint,
+            ..Default::default()
+        });
+    }
+
+    /// Adds the tiles in view to the batch
+    fn add_map(&mut self, map: &Map, camera: &Camera) {
+        let (x0, y0, x1, y1) = camera.visible();
+        for x in x0..x1 {
+            for y in y0..y1 {
+                let pos = GridPosition { x, y };
+                let cell = self.tiles[map.tile(pos).state.key()];
+                self.add(cell, camera.rect(pos), None);
+            }
+        }
+    }
+
+    fn flush(&mut self, ctx: &mut Context) -> GameResult<()> {
+        graphics::set_color(ctx, graphics::WHITE)?;
+        graphics::draw(ctx, &self.batch, graphics::Point2::new(0.0, 0.0), 0.0)?;
+        self.batch.clear();
+        Ok(())
+    }
+
+    /// Draws the map alone, e.g. in the editor
+    pub fn draw_map(&mut self, ctx: &mut Context, map: &Map, camera: &Camera) -> GameResult<()> {
+        self.add_map(map, camera);
+        self.flush(ctx)
+    }
+
+    pub fn draw(&mut self, ctx: &mut Context, map: &Map, characters: &[Character], animations: &Animations, camera: &Camera) -> GameResult<()> {
+        self.add_map(map, camera);
+        for character in characters.iter() {
+            if !animations.shown(character) {
+                continue;
+            }
+            let (x, y) = self.characters[character.class.key()];
+            let facing = match character.facing {
+                Direction::Up => 0,
+                Direction::Down => 1,
+                Direction::Left => 2,
+                Direction::Right => 3,
+            };
+            let pos = animations.position(character);
+            let tint = animations.tint(character.id).unwrap_or(TEAM_TINTS[character.team].into());
+            self.add((x + facing, y), camera.rect_at(pos.x, pos.y), Some(tint));
+        }
+        self.flush(ctx)
+    }
+}
@@ -0,0 +1,256 @@
+use std::path::Path;
+
+use ggez::{GameError, GameResult};
+
+use objective::{Outcome, Record};
+
+use super::{data, Character, Class, Direction, Event, GameState, GridPosition, Status, Tile, TileState, NB_TEAM, NO_CHAR};
+
+/// Characters put on the map by a hook start like a new roster unit
+const SPAWN_HP: u8 = 5;
+const SPAWN_MOBI: u8 = 3;
+
+/// How many of the last messages the HUD shows
+pub const MESSAGES_SHOWN: usize = 3;
+
+/// What can set a hook off
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Trigger {
+    /// A character starts its turn, the first one included
+    TurnStart,
+    /// A character dies
+    Death,
+    /// A character walks onto a tile
+    TileEntered,
+    /// A trap goes off under a character
+    TrapTriggered,
+}
+
+impl Trigger {
+    const ALL: [Trigger; 4] = [Trigger::TurnStart, Trigger::Death, Trigger::TileEntered, Trigger::TrapTriggered];
+
+    /// The section name of its hooks in trigger files
+    fn key(&self) -> &'static str {
+        match *self {
+            Trigger::TurnStart => "turn_start",
+            Trigger::Death => "death",
+            Trigger::TileEntered => "tile_entered",
+            Trigger::TrapTriggered => "trap_triggered",
+        }
+    }
+}
+
+/// What a hook can do
+#[derive(Debug, Clone)]
+pub enum Action {
+    /// Shows a line of text on the HUD
+    Message(String),
+    /// Puts a new character on a free tile
+    Spawn { class: Class, team: usize, pos: GridPosition, name: String },
+    /// Changes a tile, unless it would wall someone in
+    SetTile { pos: GridPosition, state: TileState },
+    /// Ends the game, with a winner or none
+    Victory(Option<usize>),
+}
+
+/// Actions run in order when their trigger happens and the conditions hold
+#[derive(Debug, Clone)]
+struct Hook {
+    trigger: Trigger,
+    /// The character concerned must be of this team, or have this name
+    team: Option<usize>,
+    unit: Option<String>,
+    /// It must happen on this turn, or on this tile
+    turn: Option<u32>,
+    tile: Option<GridPosition>,
+    /// Whether it runs every time, and not just the first
+    repeat: bool,
+    fired: bool,
+    actions: Vec<Action>,
+}
+
+/// The hooks of a scenario, to build puzzles and tutorials out of data files.
+/// They are declarative: a fixed set of triggers, conditions and actions, with
+/// no variables nor expressions. What they cannot say needs new code here.
+#[derive(Debug, Clone, Default)]
+pub struct Triggers {
+    hooks: Vec<Hook>,
+}
+
+impl Triggers {
+    pub fn load<P: AsRef<Path>>(path: P) -> GameResult<Triggers> {
+        Triggers::from_sections(&data::load(path)?)
+    }
+
+    /// A section per hook, named after its trigger: `turn_start`, `death`,
+    /// `tile_entered` or `trap_triggered`. Its conditions are `team`, `unit`,
+    /// `turn`, `tile = x y` and `repeat`, its actions, run in the order of
+    /// the file, `message = text`, `spawn = class team x y name`,
+    /// `set_tile = x y tile` and `victory = team` or `victory = none`.
+    fn from_sections(sections: &[data::Section]) -> GameResult<Triggers> {
+        let mut hooks = Vec::new();
+        for section in sections.iter().filter(|section| !section.name.is_empty()) {
+            let error = |message: String| GameError::ResourceLoadError(format!("hook {}: {}", section.name, message));
+            let trigger = Trigger::ALL.iter().cloned().find(|trigger| trigger.key() == section.name)
+                .ok_or_else(|| error(String::from("unknown trigger")))?;
+            let mut hook = Hook {
+                trigger: trigger,
+                team: None,
+                unit: None,
+                turn: None,
+                tile: None,
+                repeat: section.parse_or("repeat", false)?,
+                fired: false,
+                actions: Vec::new(),
+            };
+            for &(ref key, ref value) in section.entries.iter() {
+                match key.as_str() {
+                    "team" => hook.team = Some(team(value).map_err(&error)?),
+                    "unit" => hook.unit = Some(value.clone()),
+                    "turn" => hook.turn = Some(value.parse().map_err(|_| error(format!("bad turn {}", value)))?),
+                    "tile" => hook.tile = Some(tile(value)?),
+                    "repeat" => (),
+                    "message" => hook.actions.push(Action::Message(value.clone())),
+                    "spawn" => {
+                        let words: Vec<&str> = value.split_whitespace().collect();
+                        if words.len() < 4 {
+                            return Err(error(format!("spawn needs a class, a team and a tile: {}", value)));
+                        }
+                        let class = Class::from_key(words[0]).ok_or_else(|| error(format!("unknown class {}", words[0])))?;
+                        let name = if words.len() > 4 { words[4..].join(" ") } else { words[0].to_string() };
+                        hook.actions.push(Action::Spawn {
+                            class: class,
+                            team: team(words[1]).map_err(&error)?,
+                            pos: tile(&words[2..4].join(" "))?,
+                            name: name,
+                        });
+                    },
+                    "set_tile" => {
+                        let words: Vec<&str> = value.split_whitespace().collect();
+                        let state = words.get(2).and_then(|&key| TileState::ALL.iter().cloned().find(|state| state.key() == key))
+                            .ok_or_else(|| error(format!("set_tile needs a tile and a known state: {}", value)))?;
+                        hook.actions.push(Action::SetTile { pos: tile(&words[..2].join(" "))?, state: state });
+                    },
+                    "victory" => {
+                        let winner = if value == "none" { None } else { Some(team(value).map_err(&error)?) };
+                        hook.actions.push(Action::Victory(winner));
+                    },
+                    other => return Err(error(format!("unknown entry {}", other))),
+                }
+            }
+            hooks.push(hook);
+        }
+        Ok(Triggers { hooks: hooks })
+    }
+
+    /// Every hook can run again, for a new game
+    pub fn rearm(&mut self) {
+        for hook in self.hooks.iter_mut() {
+            hook.fired = false;
+        }
+    }
+
+    /// The actions of the hooks set off by `trigger`, which happened to
+    /// `character` on `pos` during `turn`
+    fn fire(&mut self, trigger: Trigger, character: &Character, pos: GridPosition, turn: u32) -> Vec<Action> {
+        let mut actions = Vec::new();
+        for hook in self.hooks.iter_mut() {
+            let holds = hook.trigger == trigger
+                && (hook.repeat || !hook.fired)
+                && hook.team.map_or(true, |team| team == character.team)
+                && hook.unit.as_ref().map_or(true, |unit| *unit == character.name)
+                && hook.turn.map_or(true, |hook_turn| hook_turn == turn)
+                && hook.tile.map_or(true, |tile| tile == pos);
+            if holds {
+                hook.fired = true;
+                actions.extend(hook.actions.iter().cloned());
+            }
+        }
+        actions
+    }
+}
+
+fn tile(value: &str) -> GameResult<GridPosition> {
+    let (x, y) = data::pair(value)?;
+    Ok(GridPosition { x: x, y: y })
+}
+
+fn team(value: &str) -> Result<usize, String> {
+    match value.parse() {
+        Ok(team) if team < NB_TEAM => Ok(team),
+        _ => Err(format!("there is no team {}", value)),
+    }
+}
+
+impl GameState {
+    /// Runs the hooks of the scenario set off by what happened to the character `id`
+    pub fn run_hooks(&mut self, trigger: Trigger, id: usize) {
+        let actions = {
+            let character = &self.characters[id];
+            match self.campaign.as_mut().and_then(|campaign| campaign.triggers_mut()) {
+                Some(triggers) => triggers.fire(trigger, character, character.pos, self.turn),
+                None => return,
+            }
+        };
+        for action in actions {
+            self.run_action(action);
+        }
+    }
+
+    fn run_action(&mut self, action: Action) {
+        match action {
+            Action::Message(text) => self.messages.push(text),
+            Action::Spawn { class, team, pos, name } => {
+                if pos.x >= self.map.width || pos.y >= self.map.height || !self.map.tile(pos).is_empty() {
+                    warn!("Cannot spawn {} at ({}, {}), the tile is taken", name, pos.x, pos.y);
+                    return;
+                }
+                let id = self.characters.len();
+                self.timeline.join(&self.characters);
+                self.characters.push(Character {
+                    id: id,
+                    unit: None,
+                    name: name,
+                    team: team,
+                    class: class,
+                    level: 1,
+                    attack: 0,
+                    pos: pos,
+                    mobi: SPAWN_MOBI,
+                    mp: SPAWN_MOBI,
+                    facing: if team == 0 { Direction::Down } else { Direction::Up },
+                    selector: pos,
+                    hp: SPAWN_HP,
+                    max_hp: SPAWN_HP,
+                    state: Status::Alive,
+                    recharge: 0,
+                    acted: false,
+                    inventory: Vec::new(),
+                    item: 0,
+                });
+                self.records.push(Record::default());
+                self.map.tile_mut(pos).char_id = id;
+                self.events.push(Event::TileChanged { pos: pos });
+            },
+            Action::SetTile { pos, state } => {
+                if pos.x >= self.map.width || pos.y >= self.map.height {
+                    warn!("Cannot change ({}, {}), it is off the map", pos.x, pos.y);
+                    return;
+                }
+                // whoever stands there must still be able to
+                let char_id = self.map.tile(pos).char_id;
+                if char_id != NO_CHAR && state.cost(self.characters[char_id].class.swims()).is_none() {
+                    warn!("Cannot change ({}, {}), {} stands there", pos.x, pos.y, self.characters[char_id].name);
+                    return;
+                }
+                let mut tile = Tile::new(state);
+                tile.char_id = char_id;
+                *self.map.tile_mut(pos) = tile;
+                self.events.push(Event::TileChanged { pos: pos });
+            },
+            Action::Victory(winner) => {
+                self.end_game(Outcome { winner: winner, reason: String::from("as the scenario has it") });
+            },
+        }
+    }
+}